@@ -1,33 +1,1541 @@
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo, mint_to};
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::metadata::{
+    create_metadata_accounts_v3, set_and_verify_sized_collection_item, CreateMetadataAccountsV3,
+    Metadata, SetAndVerifySizedCollectionItem,
+};
+use anchor_spl::token::spl_token::instruction::AuthorityType;
+use anchor_spl::token::{
+    self, Burn, CloseAccount, FreezeAccount, Mint, Token, TokenAccount, MintTo, SetAuthority,
+    ThawAccount, Transfer, mint_to, set_authority,
+};
+use mpl_token_metadata::state::DataV2;
+use game_common::{BEHAVIOR_PROGRAM_ID, COMBAT_AUTHORITY_SEED};
+use state_module::{GameConfig, CONFIG_SEED};
 
 declare_id!("Fg6PaFpoGXkYsidMpWxTWqoz1Rz4hG98bXok8eXEiN7z");
 
+pub const MINT_CONFIG_SEED: &[u8] = b"mint_config";
+// Program PDA that is the mint authority of every item mint, so items can only be minted
+// through this program.
+pub const MINT_AUTHORITY_SEED: &[u8] = b"mint_authority";
+pub const ITEM_METADATA_SEED: &[u8] = b"item_metadata";
+pub const MAX_ITEM_NAME_LEN: usize = 32;
+// Offers live at [OFFER_SEED, sender, recipient, mint], one open offer per triple, and hold
+// their items in a token account at [ESCROW_SEED, offer] owned by the offer PDA.
+pub const OFFER_SEED: &[u8] = b"offer";
+pub const ESCROW_SEED: &[u8] = b"escrow";
+// Listings live at [LISTING_SEED, seller, mint] and escrow one item each, in a token account
+// at [ESCROW_SEED, listing] owned by the listing PDA.
+pub const LISTING_SEED: &[u8] = b"listing";
+
+// Item types live at [ITEM_TYPE_SEED, type id] and cap the supply of every item whose
+// ItemMetadata has that item_type.
+pub const ITEM_TYPE_SEED: &[u8] = b"item_type";
+
+// Rarity rolled for each mint_nft item, stored at [ITEM_DATA_SEED, mint]. Tiers are common,
+// rare, epic and legendary, weighted by the drop table at [DROP_TABLE_SEED]; until the admin
+// sets one, every item is common.
+pub const ITEM_DATA_SEED: &[u8] = b"item_data";
+pub const DROP_TABLE_SEED: &[u8] = b"drop_table";
+pub const RARITY_TIERS: usize = 4;
+
+// Creator royalties on marketplace sales live at [ROYALTY_SEED, mint].
+pub const ROYALTY_SEED: &[u8] = b"royalty";
+pub const MAX_BASIS_POINTS: u16 = 10_000;
+
+// Recipes live at [RECIPE_SEED, output mint], one per craftable item.
+pub const RECIPE_SEED: &[u8] = b"recipe";
+pub const MAX_RECIPE_INGREDIENTS: usize = 4;
+
 #[program]
 pub mod asset_module {
     use super::*;
-    pub fn mint_item(ctx: Context<MintItem>, _bump: u8) -> Result<()> {
-        let mint_ctx = CpiContext::new(
+    // Admin-only, since behavior_module trusts an item's stat_bonus and rarity. Creates an item
+    // mint whose mint authority is the program PDA, along with its MintConfig and
+    // ItemMetadata, so every mintable item has attributes. The admin becomes the config's
+    // authority, the only signer mint_item accepts unless the item is `loot_only`, and the
+    // mint's freeze authority.
+    pub fn create_item(ctx: Context<CreateItem>, args: CreateItemArgs) -> Result<()> {
+        require!(!args.name.trim().is_empty(), CustomError::InvalidItemName);
+        require!(args.name.len() <= MAX_ITEM_NAME_LEN, CustomError::InvalidItemName);
+        require!(
+            args.royalty_basis_points <= MAX_BASIS_POINTS,
+            CustomError::InvalidRoyalty
+        );
+        let config = &mut ctx.accounts.mint_config;
+        config.mint = ctx.accounts.mint.key();
+        config.max_supply = args.max_supply;
+        config.minted = 0;
+        config.bump = *ctx.bumps.get("mint_config").unwrap();
+        config.authority = ctx.accounts.admin.key();
+        config.loot_only = args.loot_only;
+
+        let metadata = &mut ctx.accounts.item_metadata;
+        metadata.mint = ctx.accounts.mint.key();
+        metadata.stat_bonus = args.stat_bonus;
+        metadata.bump = *ctx.bumps.get("item_metadata").unwrap();
+        metadata.name = args.name;
+        metadata.item_type = args.item_type;
+        metadata.rarity = args.rarity;
+
+        let royalty = &mut ctx.accounts.royalty_config;
+        royalty.mint = ctx.accounts.mint.key();
+        royalty.recipient = args.royalty_recipient;
+        royalty.basis_points = args.royalty_basis_points;
+        royalty.bump = *ctx.bumps.get("royalty_config").unwrap();
+        Ok(())
+    }
+
+    // Admin-only. Each type id can be registered once.
+    pub fn register_item_type(
+        ctx: Context<RegisterItemType>,
+        type_id: u8,
+        name: String,
+        max_supply: u64,
+    ) -> Result<()> {
+        require!(!name.trim().is_empty(), CustomError::InvalidItemName);
+        require!(name.len() <= MAX_ITEM_NAME_LEN, CustomError::InvalidItemName);
+        let item_type = &mut ctx.accounts.item_type;
+        item_type.type_id = type_id;
+        item_type.name = name;
+        item_type.max_supply = max_supply;
+        item_type.minted = 0;
+        item_type.frozen = false;
+        item_type.bump = *ctx.bumps.get("item_type").unwrap();
+        Ok(())
+    }
+
+    // Admin-only. A frozen type can't be minted, crafted or dropped until unfrozen.
+    pub fn set_item_type_frozen(ctx: Context<SetItemTypeFrozen>, frozen: bool) -> Result<()> {
+        ctx.accounts.item_type.frozen = frozen;
+        Ok(())
+    }
+
+    // Changes apply to sales from then on, including listings already open.
+    pub fn set_royalty(
+        ctx: Context<SetRoyalty>,
+        recipient: Pubkey,
+        basis_points: u16,
+    ) -> Result<()> {
+        require!(basis_points <= MAX_BASIS_POINTS, CustomError::InvalidRoyalty);
+        let royalty = &mut ctx.accounts.royalty_config;
+        royalty.recipient = recipient;
+        royalty.basis_points = basis_points;
+        Ok(())
+    }
+
+    pub fn mint_item(ctx: Context<MintItem>, amount: u64) -> Result<()> {
+        ctx.accounts.mint_config.check_minter(&ctx.accounts.authority.key())?;
+        validate_mint_amount(&ctx.accounts.mint, amount)?;
+        ctx.accounts.mint_config.record_mint(amount)?;
+        ctx.accounts.item_type.record_mint(amount)?;
+        let bump = [*ctx.bumps.get("mint_authority").unwrap()];
+        let signer_seeds: &[&[&[u8]]] = &[&[MINT_AUTHORITY_SEED, &bump]];
+        let mint_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             MintTo {
                 mint: ctx.accounts.mint.to_account_info(),
                 to: ctx.accounts.to.to_account_info(),
-                authority: ctx.accounts.authority.to_account_info(),
+                authority: ctx.accounts.mint_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        mint_to(mint_ctx, amount)?;
+        Ok(())
+    }
+
+    // The mint authority PDA is also the metadata's update authority, so wallets see the item's
+    // name and symbol and only this program can change them. With the collection accounts the
+    // item is also verified into that sized collection, signed by its update authority.
+    pub fn mint_item_with_metadata(
+        ctx: Context<MintItemWithMetadata>,
+        amount: u64,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        ctx.accounts.mint_config.check_minter(&ctx.accounts.authority.key())?;
+        validate_mint_amount(&ctx.accounts.mint, amount)?;
+        ctx.accounts.mint_config.record_mint(amount)?;
+        ctx.accounts.item_type.record_mint(amount)?;
+        let bump = [*ctx.bumps.get("mint_authority").unwrap()];
+        let signer_seeds: &[&[&[u8]]] = &[&[MINT_AUTHORITY_SEED, &bump]];
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.mint_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        mint_to(mint_ctx, amount)?;
+
+        let metadata_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_metadata_program.to_account_info(),
+            CreateMetadataAccountsV3 {
+                metadata: ctx.accounts.metadata.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                mint_authority: ctx.accounts.mint_authority.to_account_info(),
+                payer: ctx.accounts.authority.to_account_info(),
+                update_authority: ctx.accounts.mint_authority.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                rent: ctx.accounts.rent.to_account_info(),
+            },
+            signer_seeds,
+        );
+        let data = DataV2 {
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points: 0,
+            creators: None,
+            collection: None,
+            uses: None,
+        };
+        create_metadata_accounts_v3(metadata_ctx, data, true, true, None)?;
+
+        let accounts = &ctx.accounts;
+        match (
+            &accounts.collection_authority,
+            &accounts.collection_mint,
+            &accounts.collection_metadata,
+            &accounts.collection_master_edition,
+        ) {
+            (None, None, None, None) => {}
+            (Some(authority), Some(mint), Some(metadata), Some(master_edition)) => {
+                let collection_ctx = CpiContext::new_with_signer(
+                    accounts.token_metadata_program.to_account_info(),
+                    SetAndVerifySizedCollectionItem {
+                        metadata: accounts.metadata.to_account_info(),
+                        collection_authority: authority.to_account_info(),
+                        payer: accounts.authority.to_account_info(),
+                        update_authority: accounts.mint_authority.to_account_info(),
+                        collection_mint: mint.to_account_info(),
+                        collection_metadata: metadata.to_account_info(),
+                        collection_master_edition: master_edition.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                set_and_verify_sized_collection_item(collection_ctx, None)?;
+            }
+            _ => return err!(CustomError::CollectionAccountsMissing),
+        }
+        Ok(())
+    }
+
+    // Admin-only. Weights are relative, one per rarity tier, and apply to later mint_nft calls.
+    pub fn set_drop_table(ctx: Context<SetDropTable>, weights: [u16; RARITY_TIERS]) -> Result<()> {
+        require!(weights.iter().any(|&weight| weight > 0), CustomError::InvalidDropTable);
+        let drop_table = &mut ctx.accounts.drop_table;
+        drop_table.weights = weights;
+        drop_table.bump = *ctx.bumps.get("drop_table").unwrap();
+        Ok(())
+    }
+
+    // Admin-only, like create_item. Creates a fresh 0-decimal mint under the program's mint
+    // authority and mints its single token to the recipient, with a rarity rolled from the drop
+    // table if one is passed. With `freeze_supply` the mint authority is removed so the supply
+    // stays at 1 for good.
+    // This is the only mint path that rolls rarity. mint_item, craft_item and loot drops mint more
+    // of an item create_item made; its tokens are interchangeable, so they share the rarity the
+    // admin set in its ItemMetadata. Each mint_nft mint is a single token, so it gets its own roll.
+    pub fn mint_nft(ctx: Context<MintNft>, freeze_supply: bool) -> Result<()> {
+        let rarity = match &ctx.accounts.drop_table {
+            Some(drop_table) => {
+                let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+                // Layout: u64 entry count, then (u64 slot, [u8; 32] hash) entries, newest first.
+                // Only the slot hash goes into the seed, so the caller can't grind a tier by
+                // choosing the mint keypair; every mint in the same slot rolls the same tier.
+                let recent_hash = data.get(16..48).ok_or(CustomError::SlotHashesUnavailable)?;
+                roll_rarity(recent_hash, &drop_table.weights)
             }
+            None => 0,
+        };
+        let item_data = &mut ctx.accounts.item_data;
+        item_data.mint = ctx.accounts.mint.key();
+        item_data.rarity = rarity;
+        item_data.bump = *ctx.bumps.get("item_data").unwrap();
+
+        let bump = [*ctx.bumps.get("mint_authority").unwrap()];
+        let signer_seeds: &[&[&[u8]]] = &[&[MINT_AUTHORITY_SEED, &bump]];
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.mint_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        mint_to(mint_ctx, 1)?;
+
+        if freeze_supply {
+            let authority_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                SetAuthority {
+                    current_authority: ctx.accounts.mint_authority.to_account_info(),
+                    account_or_mint: ctx.accounts.mint.to_account_info(),
+                },
+                signer_seeds,
+            );
+            set_authority(authority_ctx, AuthorityType::MintTokens, None)?;
+        }
+        Ok(())
+    }
+
+    // Only the output item's mint config authority can define how it is crafted.
+    pub fn create_recipe(ctx: Context<CreateRecipe>, ingredients: Vec<Ingredient>) -> Result<()> {
+        require!(
+            !ingredients.is_empty() && ingredients.len() <= MAX_RECIPE_INGREDIENTS,
+            CustomError::InvalidRecipe
+        );
+        for (i, ingredient) in ingredients.iter().enumerate() {
+            require!(ingredient.amount > 0, CustomError::InvalidRecipe);
+            // One entry per mint keeps the match against remaining_accounts exact.
+            require!(
+                ingredients[..i].iter().all(|other| other.mint != ingredient.mint),
+                CustomError::InvalidRecipe
+            );
+        }
+        let recipe = &mut ctx.accounts.recipe;
+        recipe.output_mint = ctx.accounts.output_mint.key();
+        recipe.ingredients = ingredients;
+        recipe.bump = *ctx.bumps.get("recipe").unwrap();
+        Ok(())
+    }
+
+    // remaining_accounts holds an (ingredient mint, crafter's token account) pair per recipe
+    // ingredient, in recipe order. Each burn is checked as it goes; any failure, including the
+    // final mint, reverts the burns before it along with the rest of the transaction.
+    pub fn craft_item<'info>(ctx: Context<'_, '_, '_, 'info, CraftItem<'info>>) -> Result<()> {
+        let ingredients = &ctx.accounts.recipe.ingredients;
+        require!(
+            ctx.remaining_accounts.len() == ingredients.len() * 2,
+            CustomError::IngredientAccountsMismatch
+        );
+        for (ingredient, pair) in ingredients.iter().zip(ctx.remaining_accounts.chunks(2)) {
+            let (mint, from) = (&pair[0], &pair[1]);
+            require_keys_eq!(mint.key(), ingredient.mint, CustomError::WrongIngredient);
+            let holding = Account::<TokenAccount>::try_from(from)?;
+            require_keys_eq!(holding.mint, ingredient.mint, CustomError::WrongIngredient);
+            require_keys_eq!(
+                holding.owner,
+                ctx.accounts.crafter.key(),
+                CustomError::WrongIngredient
+            );
+            require!(holding.amount >= ingredient.amount, CustomError::MissingIngredient);
+            let burn_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: mint.clone(),
+                    from: from.clone(),
+                    authority: ctx.accounts.crafter.to_account_info(),
+                },
+            );
+            token::burn(burn_ctx, ingredient.amount)?;
+        }
+
+        ctx.accounts.mint_config.record_mint(1)?;
+        ctx.accounts.item_type.record_mint(1)?;
+        let bump = [*ctx.bumps.get("mint_authority").unwrap()];
+        let signer_seeds: &[&[&[u8]]] = &[&[MINT_AUTHORITY_SEED, &bump]];
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.output_mint.to_account_info(),
+                to: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.mint_authority.to_account_info(),
+            },
+            signer_seeds,
         );
         mint_to(mint_ctx, 1)?;
         Ok(())
     }
+
+    pub fn burn_item(mut ctx: Context<BurnItem>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+        let burn_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.mint.to_account_info(),
+                from: ctx.accounts.from.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            }
+        );
+        token::burn(burn_ctx, amount)?;
+        let accounts = &mut ctx.accounts;
+        match (&mut accounts.mint_config, &accounts.item_metadata, &mut accounts.item_type) {
+            (Some(mint_config), Some(item_metadata), Some(item_type)) => {
+                require!(
+                    item_type.type_id == item_metadata.item_type,
+                    CustomError::SupplyAccountsMissing
+                );
+                mint_config.record_burn(amount);
+                item_type.record_burn(amount);
+            }
+            (None, None, None) => {}
+            _ => return err!(CustomError::SupplyAccountsMissing),
+        }
+        Ok(())
+    }
+
+    // `price_lamports` is what the receiver paid for the items off-program, zero for a gift.
+    // The sender pays the item's royalty on it before the items move. list_item and buy_item
+    // record the price on-chain instead of taking the sender's word for it.
+    pub fn transfer_item(
+        ctx: Context<TransferItem>,
+        amount: u64,
+        price_lamports: u64,
+    ) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+        pay_royalty(
+            &ctx.accounts.system_program,
+            &ctx.accounts.authority,
+            &ctx.accounts.royalty_config,
+            &ctx.accounts.royalty_recipient,
+            price_lamports,
+        )?;
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.from.to_account_info(),
+                to: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            }
+        );
+        token::transfer(transfer_ctx, amount)?;
+        Ok(())
+    }
+
+    // Makes a holder's items non-transferable (bound gear, escrow) until thawed.
+    pub fn freeze_token_account(ctx: Context<SetTokenAccountFrozen>) -> Result<()> {
+        let freeze_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            FreezeAccount {
+                account: ctx.accounts.token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                authority: ctx.accounts.freeze_authority.to_account_info(),
+            }
+        );
+        token::freeze_account(freeze_ctx)?;
+        Ok(())
+    }
+
+    pub fn thaw_token_account(ctx: Context<SetTokenAccountFrozen>) -> Result<()> {
+        let thaw_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            ThawAccount {
+                account: ctx.accounts.token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                authority: ctx.accounts.freeze_authority.to_account_info(),
+            }
+        );
+        token::thaw_account(thaw_ctx)?;
+        Ok(())
+    }
+
+    // Moves the items into escrow until the recipient accepts or the sender cancels.
+    pub fn offer_item(ctx: Context<OfferItem>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+        let offer = &mut ctx.accounts.offer;
+        offer.sender = ctx.accounts.sender.key();
+        offer.recipient = ctx.accounts.recipient.key();
+        offer.mint = ctx.accounts.mint.key();
+        offer.amount = amount;
+        offer.bump = *ctx.bumps.get("offer").unwrap();
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.from.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+                authority: ctx.accounts.sender.to_account_info(),
+            }
+        );
+        token::transfer(transfer_ctx, amount)?;
+        Ok(())
+    }
+
+    pub fn accept_item(ctx: Context<AcceptItem>) -> Result<()> {
+        release_offer(
+            &ctx.accounts.offer,
+            &ctx.accounts.escrow,
+            &ctx.accounts.to,
+            &ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.token_program,
+        )
+    }
+
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        release_offer(
+            &ctx.accounts.offer,
+            &ctx.accounts.escrow,
+            &ctx.accounts.to,
+            &ctx.accounts.sender.to_account_info(),
+            &ctx.accounts.token_program,
+        )
+    }
+
+    // Escrows one item for sale at `price_lamports`.
+    pub fn list_item(ctx: Context<ListItem>, price_lamports: u64) -> Result<()> {
+        require!(price_lamports > 0, CustomError::InvalidPrice);
+        let listing = &mut ctx.accounts.listing;
+        listing.seller = ctx.accounts.seller.key();
+        listing.mint = ctx.accounts.mint.key();
+        listing.price_lamports = price_lamports;
+        listing.bump = *ctx.bumps.get("listing").unwrap();
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.from.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+                authority: ctx.accounts.seller.to_account_info(),
+            }
+        );
+        token::transfer(transfer_ctx, 1)?;
+        Ok(())
+    }
+
+    // Sellers can't buy their own listings; they delist instead. The listing closes here, so a
+    // second buy of the same listing fails. Items with a royalty config pay its cut of the
+    // price to the royalty recipient, and the seller gets the rest.
+    pub fn buy_item(ctx: Context<BuyItem>) -> Result<()> {
+        let price = ctx.accounts.listing.price_lamports;
+        let royalty = pay_royalty(
+            &ctx.accounts.system_program,
+            &ctx.accounts.buyer,
+            &ctx.accounts.royalty_config,
+            &ctx.accounts.royalty_recipient,
+            price,
+        )?;
+        pay_lamports(
+            &ctx.accounts.system_program,
+            &ctx.accounts.buyer,
+            &ctx.accounts.seller.to_account_info(),
+            price - royalty,
+        )?;
+        release_listing(
+            &ctx.accounts.listing,
+            &ctx.accounts.escrow,
+            &ctx.accounts.to,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.token_program,
+        )
+    }
+
+    pub fn delist_item(ctx: Context<DelistItem>) -> Result<()> {
+        release_listing(
+            &ctx.accounts.listing,
+            &ctx.accounts.escrow,
+            &ctx.accounts.to,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.token_program,
+        )
+    }
+}
+
+fn pay_lamports<'info>(
+    system_program: &Program<'info, System>,
+    from: &Signer<'info>,
+    to: &AccountInfo<'info>,
+    lamports: u64,
+) -> Result<()> {
+    let payment_ctx = CpiContext::new(
+        system_program.to_account_info(),
+        system_program::Transfer {
+            from: from.to_account_info(),
+            to: to.clone(),
+        },
+    );
+    system_program::transfer(payment_ctx, lamports)
+}
+
+// Items not created through create_item have no royalty config; their address-checked
+// account is then empty and the sale pays no royalty.
+fn royalty_config(info: &UncheckedAccount) -> Result<Option<RoyaltyConfig>> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    let config = RoyaltyConfig::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    Ok(Some(config))
+}
+
+// Pays the royalty due on `price` from `payer` and returns it; zero without a config.
+fn pay_royalty<'info>(
+    system_program: &Program<'info, System>,
+    payer: &Signer<'info>,
+    royalty_config_info: &UncheckedAccount<'info>,
+    recipient: &Option<UncheckedAccount<'info>>,
+    price: u64,
+) -> Result<u64> {
+    let config = match royalty_config(royalty_config_info)? {
+        Some(config) => config,
+        None => return Ok(0),
+    };
+    let royalty = config.royalty(price);
+    if royalty > 0 {
+        let recipient = recipient.as_ref().ok_or(CustomError::WrongRoyaltyRecipient)?;
+        require_keys_eq!(recipient.key(), config.recipient, CustomError::WrongRoyaltyRecipient);
+        pay_lamports(system_program, payer, &recipient.to_account_info(), royalty)?;
+    }
+    Ok(royalty)
+}
+
+fn release_offer<'info>(
+    offer: &Account<'info, Offer>,
+    escrow: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    sender: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let bump = [offer.bump];
+    let signer_seeds: &[&[u8]] = &[
+        OFFER_SEED,
+        offer.sender.as_ref(),
+        offer.recipient.as_ref(),
+        offer.mint.as_ref(),
+        &bump,
+    ];
+    release_escrow(
+        &offer.to_account_info(),
+        signer_seeds,
+        offer.amount,
+        escrow,
+        to,
+        sender,
+        token_program,
+    )
+}
+
+fn release_listing<'info>(
+    listing: &Account<'info, Listing>,
+    escrow: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    seller: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let bump = [listing.bump];
+    let signer_seeds: &[&[u8]] = &[
+        LISTING_SEED,
+        listing.seller.as_ref(),
+        listing.mint.as_ref(),
+        &bump,
+    ];
+    release_escrow(
+        &listing.to_account_info(),
+        signer_seeds,
+        1,
+        escrow,
+        to,
+        seller,
+        token_program,
+    )
+}
+
+// Pays the escrowed items out to `to` and closes the escrow account, returning its rent to
+// `rent_recipient`. The escrow's owning PDA, `authority`, is closed by the instruction's
+// `close` constraint.
+fn release_escrow<'info>(
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+    amount: u64,
+    escrow: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    rent_recipient: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let signer = &[signer_seeds];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: escrow.to_account_info(),
+            to: to.to_account_info(),
+            authority: authority.clone(),
+        },
+        signer,
+    );
+    token::transfer(transfer_ctx, amount)?;
+    let close_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: escrow.to_account_info(),
+            destination: rent_recipient.clone(),
+            authority: authority.clone(),
+        },
+        signer,
+    );
+    token::close_account(close_ctx)
+}
+
+// Picks a tier index with probability proportional to its weight over uniformly random seeds.
+// An all-zero table always yields tier 0. Drop tables hold RARITY_TIERS weights, so the index
+// fits in a u8.
+pub fn roll_rarity(seed: &[u8], weights: &[u16]) -> u8 {
+    let total: u64 = weights.iter().map(|&weight| weight as u64).sum();
+    if total == 0 {
+        return 0;
+    }
+    let digest = hash(seed).to_bytes();
+    let roll = u64::from_le_bytes(digest[..8].try_into().unwrap()) % total;
+    // roll < total, so the running sum always passes it at some tier.
+    let mut cumulative = 0u64;
+    weights
+        .iter()
+        .position(|&weight| {
+            cumulative += weight as u64;
+            roll < cumulative
+        })
+        .unwrap_or(0) as u8
+}
+
+fn validate_mint_amount(mint: &Mint, amount: u64) -> Result<()> {
+    require!(amount > 0, CustomError::InvalidAmount);
+    // Zero-decimal mints are treated as NFTs and only ever minted one at a time.
+    require!(mint.decimals > 0 || amount == 1, CustomError::NftAmountMustBeOne);
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateItemArgs {
+    pub decimals: u8,
+    pub max_supply: u64,
+    pub name: String,
+    pub item_type: u8,
+    pub rarity: u8,
+    pub stat_bonus: i16,
+    pub royalty_recipient: Pubkey,
+    // Cut of each marketplace sale, out of MAX_BASIS_POINTS.
+    pub royalty_basis_points: u16,
+    // Only mintable as loot, by behavior_module on a kill.
+    pub loot_only: bool,
+}
+
+#[derive(Accounts)]
+#[instruction(args: CreateItemArgs)]
+pub struct CreateItem<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    // Items can only be created under a registered type, whose cap then covers them.
+    #[account(
+        seeds = [ITEM_TYPE_SEED, args.item_type.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = args.decimals,
+        mint::authority = mint_authority,
+        mint::freeze_authority = admin
+    )]
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA signer for mints; holds no data.
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = MintConfig::SPACE,
+        seeds = [MINT_CONFIG_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(
+        init,
+        payer = admin,
+        space = ItemMetadata::SPACE,
+        seeds = [ITEM_METADATA_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub item_metadata: Account<'info, ItemMetadata>,
+    #[account(
+        init,
+        payer = admin,
+        space = RoyaltyConfig::SPACE,
+        seeds = [ROYALTY_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub royalty_config: Account<'info, RoyaltyConfig>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(type_id: u8)]
+pub struct RegisterItemType<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = ItemType::SPACE,
+        seeds = [ITEM_TYPE_SEED, type_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetItemTypeFrozen<'info> {
+    #[account(
+        mut,
+        seeds = [ITEM_TYPE_SEED, item_type.type_id.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRoyalty<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        has_one = authority @ CustomError::NotMintAuthority,
+        seeds = [MINT_CONFIG_SEED, royalty_config.mint.as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(
+        mut,
+        seeds = [ROYALTY_SEED, royalty_config.mint.as_ref()],
+        bump = royalty_config.bump
+    )]
+    pub royalty_config: Account<'info, RoyaltyConfig>,
 }
 
 #[derive(Accounts)]
-#[instruction(bump: u8)]
 pub struct MintItem<'info> {
+    pub authority: Signer<'info>,
+    // Pays for the recipient's token account if it doesn't exist yet.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [MINT_CONFIG_SEED, mint.key().as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    /// CHECK: PDA signer for mints; holds no data.
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    // Only mints registered through create_item have metadata, so this rejects any other mint.
+    #[account(
+        has_one = mint,
+        seeds = [ITEM_METADATA_SEED, mint.key().as_ref()],
+        bump = item_metadata.bump
+    )]
+    pub item_metadata: Account<'info, ItemMetadata>,
+    #[account(
+        mut,
+        seeds = [ITEM_TYPE_SEED, item_metadata.item_type.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    /// CHECK: any wallet can receive items; only used to derive its associated token account.
+    pub recipient: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = recipient
+    )]
+    pub to: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MintNft<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = 0,
+        mint::authority = mint_authority
+    )]
+    pub mint: Account<'info, Mint>,
+    /// CHECK: PDA signer for mints; holds no data.
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    /// CHECK: any wallet can receive items; only used to derive its associated token account.
+    pub recipient: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        associated_token::mint = mint,
+        associated_token::authority = recipient
+    )]
+    pub to: Account<'info, TokenAccount>,
+    #[account(seeds = [DROP_TABLE_SEED], bump = drop_table.bump)]
+    pub drop_table: Option<Account<'info, DropTable>>,
+    #[account(
+        init,
+        payer = admin,
+        space = ItemData::SPACE,
+        seeds = [ITEM_DATA_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub item_data: Account<'info, ItemData>,
+    /// CHECK: address-checked; read raw since the sysvar is too large to deserialize.
+    #[account(address = sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetDropTable<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = DropTable::SPACE,
+        seeds = [DROP_TABLE_SEED],
+        bump
+    )]
+    pub drop_table: Account<'info, DropTable>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// token_metadata_program must be Metaplex Token Metadata,
+// metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s.
+#[derive(Accounts)]
+pub struct MintItemWithMetadata<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [MINT_CONFIG_SEED, mint.key().as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    /// CHECK: PDA signer for mints; holds no data.
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    // Only mints registered through create_item have metadata, so this rejects any other mint.
+    #[account(
+        has_one = mint,
+        seeds = [ITEM_METADATA_SEED, mint.key().as_ref()],
+        bump = item_metadata.bump
+    )]
+    pub item_metadata: Account<'info, ItemMetadata>,
+    #[account(
+        mut,
+        seeds = [ITEM_TYPE_SEED, item_metadata.item_type.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    #[account(mut)]
+    pub to: Account<'info, TokenAccount>,
+    /// CHECK: created by the metadata program; the address is checked against its PDA.
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    // Pass all four to add the item to a collection, or none to skip it.
+    pub collection_authority: Option<Signer<'info>>,
+    pub collection_mint: Option<Account<'info, Mint>>,
+    /// CHECK: the collection's metadata; validated by the metadata program.
+    #[account(mut)]
+    pub collection_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: the collection's master edition; validated by the metadata program.
+    pub collection_master_edition: Option<UncheckedAccount<'info>>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRecipe<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub output_mint: Account<'info, Mint>,
+    // craft_item mints without check_minter, so loot-only items can't be recipe outputs.
+    #[account(
+        constraint = mint_config.mint == output_mint.key() @ CustomError::MintMismatch,
+        constraint = !mint_config.loot_only @ CustomError::UnauthorizedMintPath,
+        has_one = authority @ CustomError::NotMintAuthority,
+        seeds = [MINT_CONFIG_SEED, output_mint.key().as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = Recipe::SPACE,
+        seeds = [RECIPE_SEED, output_mint.key().as_ref()],
+        bump
+    )]
+    pub recipe: Account<'info, Recipe>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CraftItem<'info> {
+    pub crafter: Signer<'info>,
+    #[account(
+        has_one = output_mint,
+        seeds = [RECIPE_SEED, output_mint.key().as_ref()],
+        bump = recipe.bump
+    )]
+    pub recipe: Account<'info, Recipe>,
+    #[account(mut)]
+    pub output_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = mint_config.mint == output_mint.key() @ CustomError::MintMismatch,
+        seeds = [MINT_CONFIG_SEED, output_mint.key().as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Account<'info, MintConfig>,
+    #[account(
+        constraint = item_metadata.mint == output_mint.key() @ CustomError::MintMismatch,
+        seeds = [ITEM_METADATA_SEED, output_mint.key().as_ref()],
+        bump = item_metadata.bump
+    )]
+    pub item_metadata: Account<'info, ItemMetadata>,
+    #[account(
+        mut,
+        seeds = [ITEM_TYPE_SEED, item_metadata.item_type.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Account<'info, ItemType>,
+    /// CHECK: PDA signer for mints; holds no data.
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: UncheckedAccount<'info>,
+    #[account(mut, constraint = to.mint == output_mint.key() @ CustomError::MintMismatch)]
+    pub to: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BurnItem<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    #[account(mut, constraint = from.mint == mint.key() @ CustomError::MintMismatch)]
+    pub from: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    // Pass the item's config, metadata and type together to free the burned amount under both
+    // its own and its type's supply cap.
+    #[account(
+        mut,
+        has_one = mint,
+        seeds = [MINT_CONFIG_SEED, mint.key().as_ref()],
+        bump = mint_config.bump
+    )]
+    pub mint_config: Option<Account<'info, MintConfig>>,
+    #[account(
+        has_one = mint,
+        seeds = [ITEM_METADATA_SEED, mint.key().as_ref()],
+        bump = item_metadata.bump
+    )]
+    pub item_metadata: Option<Account<'info, ItemMetadata>>,
+    // Checked against item_metadata's type in burn_item.
+    #[account(
+        mut,
+        seeds = [ITEM_TYPE_SEED, item_type.type_id.to_le_bytes().as_ref()],
+        bump = item_type.bump
+    )]
+    pub item_type: Option<Account<'info, ItemType>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferItem<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
+    #[account(mut, constraint = from.mint == to.mint @ CustomError::MintMismatch)]
+    pub from: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub to: Account<'info, TokenAccount>,
+    /// CHECK: the item's royalty config if it has one; read by royalty_config.
+    #[account(seeds = [ROYALTY_SEED, from.mint.as_ref()], bump)]
+    pub royalty_config: UncheckedAccount<'info>,
+    /// CHECK: checked against the royalty config's recipient when a royalty is due.
+    #[account(mut)]
+    pub royalty_recipient: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetTokenAccountFrozen<'info> {
+    pub freeze_authority: Signer<'info>,
+    #[account(
+        constraint = mint.freeze_authority == COption::Some(freeze_authority.key())
+            @ CustomError::NotFreezeAuthority
+    )]
+    pub mint: Account<'info, Mint>,
+    #[account(mut, constraint = token_account.mint == mint.key() @ CustomError::MintMismatch)]
+    pub token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct OfferItem<'info> {
     #[account(mut)]
+    pub sender: Signer<'info>,
+    /// CHECK: any wallet can be offered items; it only seeds the offer.
+    pub recipient: UncheckedAccount<'info>,
     pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = sender,
+        space = Offer::SPACE,
+        seeds = [OFFER_SEED, sender.key().as_ref(), recipient.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(
+        init,
+        payer = sender,
+        token::mint = mint,
+        token::authority = offer,
+        seeds = [ESCROW_SEED, offer.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = from.mint == mint.key() @ CustomError::MintMismatch)]
+    pub from: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptItem<'info> {
+    pub recipient: Signer<'info>,
+    /// CHECK: receives the offer and escrow rent; address-checked.
+    #[account(mut, address = offer.sender)]
+    pub sender: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = sender,
+        has_one = recipient @ CustomError::NotOfferRecipient,
+        seeds = [OFFER_SEED, offer.sender.as_ref(), offer.recipient.as_ref(), offer.mint.as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(mut, seeds = [ESCROW_SEED, offer.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = to.mint == offer.mint @ CustomError::MintMismatch)]
+    pub to: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
     #[account(mut)]
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        close = sender,
+        has_one = sender @ CustomError::NotOfferSender,
+        seeds = [OFFER_SEED, offer.sender.as_ref(), offer.recipient.as_ref(), offer.mint.as_ref()],
+        bump = offer.bump
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(mut, seeds = [ESCROW_SEED, offer.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = to.mint == offer.mint @ CustomError::MintMismatch)]
     pub to: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+pub struct ListItem<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = seller,
+        space = Listing::SPACE,
+        seeds = [LISTING_SEED, seller.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        init,
+        payer = seller,
+        token::mint = mint,
+        token::authority = listing,
+        seeds = [ESCROW_SEED, listing.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = from.mint == mint.key() @ CustomError::MintMismatch)]
+    pub from: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct BuyItem<'info> {
+    #[account(mut, constraint = buyer.key() != listing.seller @ CustomError::CannotBuyOwnListing)]
+    pub buyer: Signer<'info>,
+    /// CHECK: receives the payment and the listing and escrow rent; address-checked.
+    #[account(mut, address = listing.seller)]
+    pub seller: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [LISTING_SEED, listing.seller.as_ref(), listing.mint.as_ref()],
+        bump = listing.bump
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(mut, seeds = [ESCROW_SEED, listing.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = to.mint == listing.mint @ CustomError::MintMismatch)]
+    pub to: Account<'info, TokenAccount>,
+    /// CHECK: the item's royalty config if it has one; read by royalty_config.
+    #[account(seeds = [ROYALTY_SEED, listing.mint.as_ref()], bump)]
+    pub royalty_config: UncheckedAccount<'info>,
+    /// CHECK: checked against the royalty config's recipient when a royalty is due.
+    #[account(mut)]
+    pub royalty_recipient: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DelistItem<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(
+        mut,
+        close = seller,
+        has_one = seller @ CustomError::NotListingSeller,
+        seeds = [LISTING_SEED, listing.seller.as_ref(), listing.mint.as_ref()],
+        bump = listing.bump
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(mut, seeds = [ESCROW_SEED, listing.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    #[account(mut, constraint = to.mint == listing.mint @ CustomError::MintMismatch)]
+    pub to: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[account]
+pub struct Listing {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    pub price_lamports: u64,
+    pub bump: u8,
+}
+
+impl Listing {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct DropTable {
+    // Relative weight of each rarity tier, common first.
+    pub weights: [u16; RARITY_TIERS],
+    pub bump: u8,
+}
+
+impl DropTable {
+    pub const SPACE: usize = 8 + 2 * RARITY_TIERS + 1;
+}
+
+#[account]
+pub struct ItemData {
+    pub mint: Pubkey,
+    // Index into the drop table's tiers, rolled once by mint_nft.
+    pub rarity: u8,
+    pub bump: u8,
+}
+
+impl ItemData {
+    pub const SPACE: usize = 8 + 32 + 1 + 1;
+}
+
+#[account]
+pub struct ItemType {
+    pub type_id: u8,
+    pub name: String,
+    pub max_supply: u64,
+    // Across every mint of the type, less what burn_item has burned.
+    pub minted: u64,
+    pub frozen: bool,
+    pub bump: u8,
+}
+
+impl ItemType {
+    pub const SPACE: usize = 8 + 1 + 4 + MAX_ITEM_NAME_LEN + 8 + 8 + 1 + 1;
+
+    fn record_mint(&mut self, amount: u64) -> Result<()> {
+        require!(!self.frozen, CustomError::ItemTypeFrozen);
+        let minted = self
+            .minted
+            .checked_add(amount)
+            .ok_or(CustomError::ItemTypeExhausted)?;
+        require!(minted <= self.max_supply, CustomError::ItemTypeExhausted);
+        self.minted = minted;
+        Ok(())
+    }
+
+    fn record_burn(&mut self, amount: u64) {
+        self.minted = self.minted.saturating_sub(amount);
+    }
+}
+
+#[account]
+pub struct RoyaltyConfig {
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub basis_points: u16,
+    pub bump: u8,
+}
+
+impl RoyaltyConfig {
+    pub const SPACE: usize = 8 + 32 + 32 + 2 + 1;
+
+    // Rounds down, so any fraction of a lamport stays with the seller.
+    pub fn royalty(&self, price: u64) -> u64 {
+        (price as u128 * self.basis_points as u128 / MAX_BASIS_POINTS as u128) as u64
+    }
+}
+
+#[account]
+pub struct Offer {
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+impl Offer {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Ingredient {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[account]
+pub struct Recipe {
+    pub output_mint: Pubkey,
+    pub ingredients: Vec<Ingredient>,
+    pub bump: u8,
+}
+
+impl Recipe {
+    pub const SPACE: usize = 8 + 32 + 4 + (32 + 8) * MAX_RECIPE_INGREDIENTS + 1;
+}
+
+#[account]
+pub struct MintConfig {
+    pub mint: Pubkey,
+    pub max_supply: u64,
+    pub minted: u64,
+    pub bump: u8,
+    // The only signer allowed to mint this item or attach its metadata, unless loot_only.
+    pub authority: Pubkey,
+    // When set, the only accepted minter is behavior_module's combat authority PDA, which it
+    // signs for only while dropping loot.
+    pub loot_only: bool,
+}
+
+impl MintConfig {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + 1 + 32 + 1;
+
+    fn check_minter(&self, signer: &Pubkey) -> Result<()> {
+        if self.loot_only {
+            let (combat_authority, _) =
+                Pubkey::find_program_address(&[COMBAT_AUTHORITY_SEED], &BEHAVIOR_PROGRAM_ID);
+            require_keys_eq!(*signer, combat_authority, CustomError::UnauthorizedMintPath);
+        } else {
+            require_keys_eq!(*signer, self.authority, CustomError::NotMintAuthority);
+        }
+        Ok(())
+    }
+
+    fn record_mint(&mut self, amount: u64) -> Result<()> {
+        let minted = self
+            .minted
+            .checked_add(amount)
+            .ok_or(CustomError::SupplyExhausted)?;
+        require!(minted <= self.max_supply, CustomError::SupplyExhausted);
+        self.minted = minted;
+        Ok(())
+    }
+
+    fn record_burn(&mut self, amount: u64) {
+        self.minted = self.minted.saturating_sub(amount);
+    }
+}
+
+#[account]
+pub struct ItemMetadata {
+    pub mint: Pubkey,
+    // Added to the holder's attack power while the item is equipped as a weapon.
+    pub stat_bonus: i16,
+    pub bump: u8,
+    pub name: String,
+    pub item_type: u8,
+    // Shared by every token of the mint. mint_nft mints have no ItemMetadata; their rarity is
+    // rolled into ItemData instead.
+    pub rarity: u8,
+}
+
+impl ItemMetadata {
+    pub const SPACE: usize = 8 + 32 + 2 + 1 + 4 + MAX_ITEM_NAME_LEN + 1 + 1;
+}
+
+#[error_code]
+pub enum CustomError {
+    #[msg("Amount must be greater than zero.")]
+    InvalidAmount,
+    #[msg("Zero-decimal items can only be minted one at a time.")]
+    NftAmountMustBeOne,
+    #[msg("Token account does not belong to this mint.")]
+    MintMismatch,
+    #[msg("Minting would exceed the item's supply cap.")]
+    SupplyExhausted,
+    #[msg("Signer is not the item's mint config authority.")]
+    NotMintAuthority,
+    #[msg("Signer is not the mint's freeze authority.")]
+    NotFreezeAuthority,
+    #[msg("Only the offer's recipient can accept it.")]
+    NotOfferRecipient,
+    #[msg("Only the offer's sender can cancel it.")]
+    NotOfferSender,
+    #[msg("Item name must be 1 to MAX_ITEM_NAME_LEN bytes.")]
+    InvalidItemName,
+    #[msg("A recipe needs 1 to MAX_RECIPE_INGREDIENTS distinct ingredients, each above zero.")]
+    InvalidRecipe,
+    #[msg("Pass one mint and token account pair per recipe ingredient.")]
+    IngredientAccountsMismatch,
+    #[msg("Ingredient account does not match the recipe.")]
+    WrongIngredient,
+    #[msg("Not enough of a recipe ingredient.")]
+    MissingIngredient,
+    #[msg("Listing price must be greater than zero.")]
+    InvalidPrice,
+    #[msg("Sellers can't buy their own listings.")]
+    CannotBuyOwnListing,
+    #[msg("Only the listing's seller can delist it.")]
+    NotListingSeller,
+    #[msg("Royalty basis points can't exceed MAX_BASIS_POINTS.")]
+    InvalidRoyalty,
+    #[msg("Royalty recipient missing or does not match the item's royalty config.")]
+    WrongRoyaltyRecipient,
+    #[msg("Loot-only items can only be minted by behavior_module's loot drops.")]
+    UnauthorizedMintPath,
+    #[msg("Pass all of the collection accounts or none of them.")]
+    CollectionAccountsMissing,
+    #[msg("Item type is frozen.")]
+    ItemTypeFrozen,
+    #[msg("Minting would exceed the item type's supply cap.")]
+    ItemTypeExhausted,
+    #[msg("Drop table needs at least one non-zero weight.")]
+    InvalidDropTable,
+    #[msg("Slot hashes sysvar has no entries.")]
+    SlotHashesUnavailable,
+    #[msg("Pass the item's mint config, metadata and type together or none of them.")]
+    SupplyAccountsMissing,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roll_rarity_only_lands_on_weighted_tiers() {
+        for nonce in 0u64..200 {
+            let seed = nonce.to_le_bytes();
+            assert_eq!(roll_rarity(&seed, &[0, 0, 5, 0]), 2);
+            assert_ne!(roll_rarity(&seed, &[1, 0, 1, 1]), 1);
+        }
+    }
+
+    #[test]
+    fn roll_rarity_defaults_to_common_without_weights() {
+        assert_eq!(roll_rarity(b"seed", &[0; RARITY_TIERS]), 0);
+        assert_eq!(roll_rarity(b"seed", &[]), 0);
+    }
+
+    #[test]
+    fn roll_rarity_is_deterministic_and_follows_the_weights() {
+        let weights = [70, 20, 9, 1];
+        let mut counts = [0u32; RARITY_TIERS];
+        for nonce in 0u64..10_000 {
+            let seed = nonce.to_le_bytes();
+            let tier = roll_rarity(&seed, &weights);
+            assert_eq!(roll_rarity(&seed, &weights), tier);
+            counts[tier as usize] += 1;
+        }
+        // Expect 7,000 / 2,000 / 900 / 100; well outside these bands would mean a biased roll.
+        assert!((6_700..7_300).contains(&counts[0]), "common {}", counts[0]);
+        assert!((1_800..2_200).contains(&counts[1]), "rare {}", counts[1]);
+        assert!((750..1_050).contains(&counts[2]), "epic {}", counts[2]);
+        assert!((50..150).contains(&counts[3]), "legendary {}", counts[3]);
+    }
+}
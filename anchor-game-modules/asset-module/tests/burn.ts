@@ -0,0 +1,75 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { getAccount, getAssociatedTokenAddressSync } from "@solana/spl-token";
+import {
+  admin,
+  createItem,
+  expectError,
+  itemArgs,
+  itemMetadataPda,
+  itemTypePda,
+  mintConfigPda,
+  mintItemAccounts,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 5;
+
+describe("burn_item", () => {
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000);
+  });
+
+  it("frees the burned amount under both supply caps", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID, { decimals: 2, maxSupply: new BN(100) }));
+    const mintAccounts = mintItemAccounts(mint, TYPE_ID, admin);
+    await program.methods.mintItem(new BN(100)).accounts(mintAccounts).rpc();
+    const typeBefore = await program.account.itemType.fetch(itemTypePda(TYPE_ID));
+
+    const from = getAssociatedTokenAddressSync(mint, admin);
+    await program.methods
+      .burnItem(new BN(40))
+      .accounts({
+        authority: admin,
+        mint,
+        from,
+        mintConfig: mintConfigPda(mint),
+        itemMetadata: itemMetadataPda(mint),
+        itemType: itemTypePda(TYPE_ID),
+      })
+      .rpc();
+
+    const mintConfig = await program.account.mintConfig.fetch(mintConfigPda(mint));
+    const itemType = await program.account.itemType.fetch(itemTypePda(TYPE_ID));
+    expect(mintConfig.minted.toNumber()).to.equal(60);
+    expect(itemType.minted.toNumber()).to.equal(typeBefore.minted.toNumber() - 40);
+    expect((await getAccount(provider.connection, from)).amount).to.equal(BigInt(60));
+
+    // The freed supply can be minted again.
+    await program.methods.mintItem(new BN(40)).accounts(mintAccounts).rpc();
+  });
+
+  it("rejects a partial set of supply accounts", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    await program.methods
+      .mintItem(new BN(1))
+      .accounts(mintItemAccounts(mint, TYPE_ID, admin))
+      .rpc();
+    await expectError(
+      program.methods
+        .burnItem(new BN(1))
+        .accounts({
+          authority: admin,
+          mint,
+          from: getAssociatedTokenAddressSync(mint, admin),
+          mintConfig: mintConfigPda(mint),
+          itemMetadata: null,
+          itemType: null,
+        })
+        .rpc(),
+      "SupplyAccountsMissing"
+    );
+  });
+});
@@ -0,0 +1,139 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  createCreateMasterEditionV3Instruction,
+  createCreateMetadataAccountV3Instruction,
+  Metadata,
+  PROGRAM_ID as TOKEN_METADATA_PROGRAM_ID,
+} from "@metaplex-foundation/mpl-token-metadata";
+import { createAssociatedTokenAccount, createMint, mintTo } from "@solana/spl-token";
+import { Keypair, PublicKey, Transaction } from "@solana/web3.js";
+import {
+  admin,
+  createItem,
+  itemArgs,
+  itemMetadataPda,
+  itemTypePda,
+  mintAuthorityPda,
+  mintConfigPda,
+  pda,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 12;
+
+const metadataPda = (mint: PublicKey) =>
+  pda(
+    [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), mint.toBuffer()],
+    TOKEN_METADATA_PROGRAM_ID
+  );
+const masterEditionPda = (mint: PublicKey) =>
+  pda(
+    [
+      Buffer.from("metadata"),
+      TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+      mint.toBuffer(),
+      Buffer.from("edition"),
+    ],
+    TOKEN_METADATA_PROGRAM_ID
+  );
+
+describe("item collections", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+  let collectionMint: PublicKey;
+
+  // A sized collection NFT whose update authority is the admin.
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+    collectionMint = await createMint(provider.connection, payer, admin, admin, 0);
+    const ata = await createAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      collectionMint,
+      admin
+    );
+    await mintTo(provider.connection, payer, collectionMint, ata, payer, 1);
+    const accounts = {
+      metadata: metadataPda(collectionMint),
+      mint: collectionMint,
+      mintAuthority: admin,
+      payer: admin,
+      updateAuthority: admin,
+    };
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createCreateMetadataAccountV3Instruction(accounts, {
+          createMetadataAccountArgsV3: {
+            data: {
+              name: "Armory",
+              symbol: "ARM",
+              uri: "",
+              sellerFeeBasisPoints: 0,
+              creators: null,
+              collection: null,
+              uses: null,
+            },
+            isMutable: true,
+            collectionDetails: { __kind: "V1", size: 0 },
+          },
+        }),
+        createCreateMasterEditionV3Instruction(
+          { ...accounts, edition: masterEditionPda(collectionMint) },
+          { createMasterEditionArgs: { maxSupply: 0 } }
+        )
+      )
+    );
+  });
+
+  // Creates an item, opens a token account for a fresh recipient and mints one with metadata,
+  // verified into the collection when `inCollection` is set.
+  async function mintWithMetadata(inCollection: boolean) {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    const to = await createAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      mint,
+      Keypair.generate().publicKey
+    );
+    await program.methods
+      .mintItemWithMetadata(new BN(1), "Relic", "RLC", "https://example.com/relic.json")
+      .accounts({
+        authority: admin,
+        mint,
+        mintConfig: mintConfigPda(mint),
+        mintAuthority: mintAuthorityPda,
+        itemMetadata: itemMetadataPda(mint),
+        itemType: itemTypePda(TYPE_ID),
+        to,
+        metadata: metadataPda(mint),
+        collectionAuthority: inCollection ? admin : null,
+        collectionMint: inCollection ? collectionMint : null,
+        collectionMetadata: inCollection ? metadataPda(collectionMint) : null,
+        collectionMasterEdition: inCollection ? masterEditionPda(collectionMint) : null,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+      })
+      .rpc();
+    return Metadata.fromAccountAddress(provider.connection, metadataPda(mint));
+  }
+
+  it("verifies the item into the collection", async () => {
+    const metadata = await mintWithMetadata(true);
+    expect(metadata.collection?.key.toBase58()).to.equal(collectionMint.toBase58());
+    expect(metadata.collection?.verified).to.be.true;
+    expect(metadata.updateAuthority.toBase58()).to.equal(mintAuthorityPda.toBase58());
+
+    const collection = await Metadata.fromAccountAddress(
+      provider.connection,
+      metadataPda(collectionMint)
+    );
+    expect(Number(collection.collectionDetails?.size)).to.equal(1);
+  });
+
+  it("leaves the collection unset without the collection accounts", async () => {
+    const metadata = await mintWithMetadata(false);
+    expect(metadata.collection).to.be.null;
+    expect(metadata.data.name.replace(/\0/g, "")).to.equal("Relic");
+  });
+});
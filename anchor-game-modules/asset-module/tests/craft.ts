@@ -0,0 +1,137 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  createAssociatedTokenAccount,
+  getAccount,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  admin,
+  createItem,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  itemMetadataPda,
+  itemTypePda,
+  mintAuthorityPda,
+  mintConfigPda,
+  mintItemTo,
+  pda,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 8;
+
+const recipePda = (outputMint: PublicKey) => pda([Buffer.from("recipe"), outputMint.toBuffer()]);
+
+describe("crafting", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+  let ore: PublicKey;
+  let wood: PublicKey;
+  let output: PublicKey;
+
+  const createRecipe = (outputMint: PublicKey) =>
+    program.methods
+      .createRecipe([
+        { mint: ore, amount: new BN(2) },
+        { mint: wood, amount: new BN(1) },
+      ])
+      .accounts({
+        authority: admin,
+        outputMint,
+        mintConfig: mintConfigPda(outputMint),
+        recipe: recipePda(outputMint),
+      })
+      .rpc();
+
+  // The recipe takes two ore and one wood.
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+    ore = await createItem(itemArgs(TYPE_ID, { name: "ore" }));
+    wood = await createItem(itemArgs(TYPE_ID, { name: "wood" }));
+    output = await createItem(itemArgs(TYPE_ID, { name: "axe" }));
+    await createRecipe(output);
+  });
+
+  // A fresh crafter holding `oreAmount` ore and one wood, with an empty output token account.
+  async function newCrafter(oreAmount: number) {
+    const crafter = await fundedKeypair();
+    await mintItemTo(ore, TYPE_ID, crafter.publicKey, oreAmount);
+    await mintItemTo(wood, TYPE_ID, crafter.publicKey);
+    const to = await createAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      output,
+      crafter.publicKey
+    );
+    return { crafter, to };
+  }
+
+  const held = async (mint: PublicKey, owner: PublicKey) =>
+    (await getAccount(provider.connection, getAssociatedTokenAddressSync(mint, owner))).amount;
+
+  const craft = (crafter: Keypair, to: PublicKey, ingredients: PublicKey[]) =>
+    program.methods
+      .craftItem()
+      .accounts({
+        crafter: crafter.publicKey,
+        recipe: recipePda(output),
+        outputMint: output,
+        mintConfig: mintConfigPda(output),
+        itemMetadata: itemMetadataPda(output),
+        itemType: itemTypePda(TYPE_ID),
+        mintAuthority: mintAuthorityPda,
+        to,
+      })
+      .remainingAccounts(
+        ingredients.flatMap((mint) => [
+          { pubkey: mint, isWritable: true, isSigner: false },
+          {
+            pubkey: getAssociatedTokenAddressSync(mint, crafter.publicKey),
+            isWritable: true,
+            isSigner: false,
+          },
+        ])
+      )
+      .signers([crafter])
+      .rpc();
+
+  it("burns the ingredients and mints the output", async () => {
+    const { crafter, to } = await newCrafter(2);
+
+    await craft(crafter, to, [ore, wood]);
+    expect(await held(ore, crafter.publicKey)).to.equal(BigInt(0));
+    expect(await held(wood, crafter.publicKey)).to.equal(BigInt(0));
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+
+  it("rejects a crafter short of an ingredient", async () => {
+    const { crafter, to } = await newCrafter(1);
+
+    await expectError(craft(crafter, to, [ore, wood]), "MissingIngredient");
+    expect(await held(ore, crafter.publicKey)).to.equal(BigInt(1));
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(0));
+  });
+
+  it("rejects a substituted mint without burning anything", async () => {
+    const { crafter, to } = await newCrafter(2);
+    const bark = await createItem(itemArgs(TYPE_ID, { name: "bark" }));
+    await mintItemTo(bark, TYPE_ID, crafter.publicKey);
+
+    await expectError(craft(crafter, to, [ore, bark]), "WrongIngredient");
+    // The ore burn ran before the bad pair was reached and was reverted with it.
+    expect(await held(ore, crafter.publicKey)).to.equal(BigInt(2));
+    expect(await held(bark, crafter.publicKey)).to.equal(BigInt(1));
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(0));
+  });
+
+  it("rejects a recipe for a loot-only item", async () => {
+    const trophy = await createItem(itemArgs(TYPE_ID, { name: "trophy", lootOnly: true }));
+
+    await expectError(createRecipe(trophy), "UnauthorizedMintPath");
+    expect(await provider.connection.getAccountInfo(recipePda(trophy))).to.be.null;
+  });
+});
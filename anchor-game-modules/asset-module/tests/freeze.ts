@@ -0,0 +1,85 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { createAssociatedTokenAccount, getAccount } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  admin,
+  createItem,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  mintItemTo,
+  program,
+  provider,
+  registerItemType,
+  royaltyPda,
+} from "./helpers";
+
+const TYPE_ID = 11;
+// SPL Token's AccountFrozen.
+const ACCOUNT_FROZEN = "0x11";
+
+describe("token account freezing", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+  });
+
+  // Items are created with the admin as their mint's freeze authority.
+  const setFrozen = (frozen: boolean, mint: PublicKey, tokenAccount: PublicKey) =>
+    (frozen ? program.methods.freezeTokenAccount() : program.methods.thawTokenAccount())
+      .accounts({ freezeAuthority: admin, mint, tokenAccount })
+      .rpc();
+
+  // A gift, so no royalty is due.
+  const transfer = (authority: Keypair, mint: PublicKey, from: PublicKey, to: PublicKey) =>
+    program.methods
+      .transferItem(new BN(1), new BN(0))
+      .accounts({
+        authority: authority.publicKey,
+        from,
+        to,
+        royaltyConfig: royaltyPda(mint),
+        royaltyRecipient: null,
+      })
+      .signers([authority])
+      .rpc();
+
+  it("blocks transfers out of a frozen account until it is thawed", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    const owner = await fundedKeypair();
+    const from = await mintItemTo(mint, TYPE_ID, owner.publicKey, 2);
+    const to = await createAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      mint,
+      Keypair.generate().publicKey
+    );
+
+    await setFrozen(true, mint, from);
+    await expectError(transfer(owner, mint, from, to), ACCOUNT_FROZEN);
+    expect((await getAccount(provider.connection, from)).amount).to.equal(BigInt(2));
+
+    await setFrozen(false, mint, from);
+    await transfer(owner, mint, from, to);
+    expect((await getAccount(provider.connection, from)).amount).to.equal(BigInt(1));
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+
+  it("only lets the mint's freeze authority freeze", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    const owner = await fundedKeypair();
+    const from = await mintItemTo(mint, TYPE_ID, owner.publicKey);
+
+    await expectError(
+      program.methods
+        .freezeTokenAccount()
+        .accounts({ freezeAuthority: owner.publicKey, mint, tokenAccount: from })
+        .signers([owner])
+        .rpc(),
+      "NotFreezeAuthority"
+    );
+    expect((await getAccount(provider.connection, from)).isFrozen).to.be.false;
+  });
+});
@@ -0,0 +1,199 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Idl, Program } from "@coral-xyz/anchor";
+import { getAssociatedTokenAddressSync } from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import { AssetModule } from "../target/types/asset_module";
+import stateIdl from "../../state-module/target/idl/state_module.json";
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+
+export const program = anchor.workspace.AssetModule as Program<AssetModule>;
+export const stateProgram = new Program(
+  stateIdl as Idl,
+  new PublicKey(stateIdl.metadata.address),
+  provider
+);
+export const admin = provider.wallet.publicKey;
+
+export const pda = (seeds: (Buffer | Uint8Array)[], programId = program.programId) =>
+  PublicKey.findProgramAddressSync(seeds, programId)[0];
+
+export const configPda = pda([Buffer.from("config")], stateProgram.programId);
+export const stateProgramDataPda = PublicKey.findProgramAddressSync(
+  [stateProgram.programId.toBuffer()],
+  new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+)[0];
+export const mintAuthorityPda = pda([Buffer.from("mint_authority")]);
+export const mintConfigPda = (mint: PublicKey) =>
+  pda([Buffer.from("mint_config"), mint.toBuffer()]);
+export const itemMetadataPda = (mint: PublicKey) =>
+  pda([Buffer.from("item_metadata"), mint.toBuffer()]);
+export const royaltyPda = (mint: PublicKey) => pda([Buffer.from("royalty"), mint.toBuffer()]);
+export const itemTypePda = (typeId: number) =>
+  pda([Buffer.from("item_type"), Buffer.from([typeId])]);
+export const listingPda = (seller: PublicKey, mint: PublicKey) =>
+  pda([Buffer.from("listing"), seller.toBuffer(), mint.toBuffer()]);
+// Escrow token account of a listing or an offer.
+export const escrowPda = (record: PublicKey) => pda([Buffer.from("escrow"), record.toBuffer()]);
+
+export async function fundedKeypair(sol = 10): Promise<Keypair> {
+  const keypair = Keypair.generate();
+  const signature = await provider.connection.requestAirdrop(
+    keypair.publicKey,
+    sol * LAMPORTS_PER_SOL
+  );
+  await provider.connection.confirmTransaction(signature);
+  return keypair;
+}
+
+// Creates state_module's GameConfig the first time any test file needs it. The provider wallet
+// is state_module's upgrade authority (see Anchor.toml) and the game admin.
+export async function ensureGame() {
+  if (await provider.connection.getAccountInfo(configPda)) {
+    return;
+  }
+  await stateProgram.methods
+    .initializeConfig({
+      admin,
+      maxLevel: 100,
+      baseHp: 100,
+      xpMultiplier: 100,
+      attackCooldownSecs: new BN(0),
+    })
+    .accounts({
+      config: configPda,
+      authority: admin,
+      program: stateProgram.programId,
+      programData: stateProgramDataPda,
+    })
+    .rpc();
+}
+
+// Type ids are global, so each test file registers its own.
+export async function registerItemType(typeId: number, maxSupply: number) {
+  await ensureGame();
+  await program.methods
+    .registerItemType(typeId, `type-${typeId}`, new BN(maxSupply))
+    .accounts({ itemType: itemTypePda(typeId), config: configPda, admin })
+    .rpc();
+}
+
+export type ItemArgs = {
+  decimals: number;
+  maxSupply: BN;
+  name: string;
+  itemType: number;
+  rarity: number;
+  statBonus: number;
+  royaltyRecipient: PublicKey;
+  royaltyBasisPoints: number;
+  lootOnly: boolean;
+};
+
+export const itemArgs = (itemType: number, overrides: Partial<ItemArgs> = {}): ItemArgs => ({
+  decimals: 0,
+  maxSupply: new BN(10),
+  name: "sword",
+  itemType,
+  rarity: 0,
+  statBonus: 0,
+  royaltyRecipient: admin,
+  royaltyBasisPoints: 0,
+  lootOnly: false,
+  ...overrides,
+});
+
+export const createItemAccounts = (mint: PublicKey, itemType: number, signer: PublicKey) => ({
+  admin: signer,
+  config: configPda,
+  itemType: itemTypePda(itemType),
+  mint,
+  mintAuthority: mintAuthorityPda,
+  mintConfig: mintConfigPda(mint),
+  itemMetadata: itemMetadataPda(mint),
+  royaltyConfig: royaltyPda(mint),
+});
+
+// Creates an item as the admin and returns its mint.
+export async function createItem(args: ItemArgs): Promise<PublicKey> {
+  await ensureGame();
+  const mint = Keypair.generate();
+  await program.methods
+    .createItem(args)
+    .accounts(createItemAccounts(mint.publicKey, args.itemType, admin))
+    .signers([mint])
+    .rpc();
+  return mint.publicKey;
+}
+
+export const mintItemAccounts = (mint: PublicKey, itemType: number, recipient: PublicKey) => ({
+  authority: admin,
+  payer: admin,
+  mint,
+  mintConfig: mintConfigPda(mint),
+  mintAuthority: mintAuthorityPda,
+  itemMetadata: itemMetadataPda(mint),
+  itemType: itemTypePda(itemType),
+  recipient,
+  to: getAssociatedTokenAddressSync(mint, recipient),
+});
+
+// Mints `amount` of an item to `recipient` as the admin and returns their token account.
+export async function mintItemTo(
+  mint: PublicKey,
+  itemType: number,
+  recipient: PublicKey,
+  amount = 1
+): Promise<PublicKey> {
+  const accounts = mintItemAccounts(mint, itemType, recipient);
+  await program.methods.mintItem(new BN(amount)).accounts(accounts).rpc();
+  return accounts.to;
+}
+
+// Lists one item from `seller`'s token account and returns the listing.
+export async function listItem(seller: Keypair, mint: PublicKey, price: BN): Promise<PublicKey> {
+  const listing = listingPda(seller.publicKey, mint);
+  await program.methods
+    .listItem(price)
+    .accounts({
+      seller: seller.publicKey,
+      mint,
+      listing,
+      escrow: escrowPda(listing),
+      from: getAssociatedTokenAddressSync(mint, seller.publicKey),
+    })
+    .signers([seller])
+    .rpc();
+  return listing;
+}
+
+export const buyItemAccounts = (
+  listing: PublicKey,
+  seller: PublicKey,
+  mint: PublicKey,
+  buyer: PublicKey,
+  royaltyRecipient: PublicKey | null
+) => ({
+  buyer,
+  seller,
+  listing,
+  escrow: escrowPda(listing),
+  to: getAssociatedTokenAddressSync(mint, buyer),
+  royaltyConfig: royaltyPda(mint),
+  royaltyRecipient,
+});
+
+// Resolves once `promise` fails with the named program error.
+export async function expectError(promise: Promise<unknown>, code: string) {
+  try {
+    await promise;
+  } catch (err) {
+    const actual = err instanceof anchor.AnchorError ? err.error.errorCode.code : String(err);
+    if (!actual.includes(code)) {
+      throw new Error(`expected ${code}, got ${actual}`);
+    }
+    return;
+  }
+  throw new Error(`expected ${code}, but the transaction succeeded`);
+}
@@ -0,0 +1,58 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import {
+  admin,
+  configPda,
+  createItem,
+  expectError,
+  itemArgs,
+  itemTypePda,
+  mintItemAccounts,
+  program,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 6;
+const CAPPED_TYPE_ID = 7;
+
+const setFrozen = (typeId: number, frozen: boolean) =>
+  program.methods
+    .setItemTypeFrozen(frozen)
+    .accounts({ itemType: itemTypePda(typeId), config: configPda, admin })
+    .rpc();
+
+const mintOne = (mint: PublicKey, typeId: number) =>
+  program.methods.mintItem(new BN(1)).accounts(mintItemAccounts(mint, typeId, admin)).rpc();
+
+const mintedOf = async (typeId: number) =>
+  (await program.account.itemType.fetch(itemTypePda(typeId))).minted.toNumber();
+
+describe("item types", () => {
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+    await registerItemType(CAPPED_TYPE_ID, 2);
+  });
+
+  it("stops mints of a frozen type until it is unfrozen", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID));
+
+    await setFrozen(TYPE_ID, true);
+    await expectError(mintOne(mint, TYPE_ID), "ItemTypeFrozen");
+    expect(await mintedOf(TYPE_ID)).to.equal(0);
+
+    await setFrozen(TYPE_ID, false);
+    await mintOne(mint, TYPE_ID);
+    expect(await mintedOf(TYPE_ID)).to.equal(1);
+  });
+
+  it("caps mints across every item of the type", async () => {
+    const first = await createItem(itemArgs(CAPPED_TYPE_ID));
+    const second = await createItem(itemArgs(CAPPED_TYPE_ID));
+
+    await mintOne(first, CAPPED_TYPE_ID);
+    await mintOne(second, CAPPED_TYPE_ID);
+    await expectError(mintOne(first, CAPPED_TYPE_ID), "ItemTypeExhausted");
+    expect(await mintedOf(CAPPED_TYPE_ID)).to.equal(2);
+  });
+});
@@ -0,0 +1,55 @@
+import { expect } from "chai";
+import { Keypair } from "@solana/web3.js";
+import {
+  admin,
+  createItem,
+  createItemAccounts,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  itemMetadataPda,
+  program,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 1;
+const UNREGISTERED_TYPE_ID = 255;
+
+describe("items", () => {
+  before(async () => {
+    await registerItemType(TYPE_ID, 100);
+  });
+
+  it("rejects items created by anyone but the admin", async () => {
+    const stranger = await fundedKeypair();
+    const mint = Keypair.generate();
+    await expectError(
+      program.methods
+        .createItem(itemArgs(TYPE_ID, { statBonus: 32767, rarity: 3 }))
+        .accounts(createItemAccounts(mint.publicKey, TYPE_ID, stranger.publicKey))
+        .signers([stranger, mint])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+  });
+
+  it("rejects items under a type the admin hasn't registered", async () => {
+    const mint = Keypair.generate();
+    await expectError(
+      program.methods
+        .createItem(itemArgs(UNREGISTERED_TYPE_ID))
+        .accounts(createItemAccounts(mint.publicKey, UNREGISTERED_TYPE_ID, admin))
+        .signers([mint])
+        .rpc(),
+      "AccountNotInitialized"
+    );
+  });
+
+  it("stores the admin's stat bonus and rarity", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID, { statBonus: 5, rarity: 2 }));
+    const metadata = await program.account.itemMetadata.fetch(itemMetadataPda(mint));
+    expect(metadata.statBonus).to.equal(5);
+    expect(metadata.rarity).to.equal(2);
+    expect(metadata.itemType).to.equal(TYPE_ID);
+  });
+});
@@ -0,0 +1,113 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  createAssociatedTokenAccount,
+  getAccount,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import {
+  buyItemAccounts,
+  createItem,
+  escrowPda,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  listItem,
+  mintItemTo,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 9;
+const PRICE = new BN(LAMPORTS_PER_SOL / 2);
+
+describe("marketplace", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+  const balance = (key: PublicKey) => provider.connection.getBalance(key);
+
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+  });
+
+  // A fresh seller holding one item with no royalty. The provider wallet pays every fee.
+  async function newSeller() {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    const seller = await fundedKeypair();
+    await mintItemTo(mint, TYPE_ID, seller.publicKey);
+    return { mint, seller };
+  }
+
+  async function newBuyer(mint: PublicKey) {
+    const buyer = await fundedKeypair();
+    await createAssociatedTokenAccount(provider.connection, payer, mint, buyer.publicKey);
+    return buyer;
+  }
+
+  const buy = (listing: PublicKey, seller: PublicKey, mint: PublicKey, buyer: Keypair) =>
+    program.methods
+      .buyItem()
+      .accounts(buyItemAccounts(listing, seller, mint, buyer.publicKey, null))
+      .signers([buyer])
+      .rpc();
+
+  it("rejects a listing priced at zero", async () => {
+    const { mint, seller } = await newSeller();
+    await expectError(listItem(seller, mint, new BN(0)), "InvalidPrice");
+  });
+
+  it("rejects the seller buying their own listing", async () => {
+    const { mint, seller } = await newSeller();
+    const listing = await listItem(seller, mint, PRICE);
+    await expectError(buy(listing, seller.publicKey, mint, seller), "CannotBuyOwnListing");
+  });
+
+  it("pays the seller, hands over the item and reclaims the escrow rent", async () => {
+    const { mint, seller } = await newSeller();
+    const sellerBefore = await balance(seller.publicKey);
+    const listing = await listItem(seller, mint, PRICE);
+    const escrow = escrowPda(listing);
+    const buyer = await newBuyer(mint);
+    const buyerBefore = await balance(buyer.publicKey);
+
+    await buy(listing, seller.publicKey, mint, buyer);
+    // The listing and escrow rent the seller put up comes back with the price.
+    expect(await balance(seller.publicKey)).to.equal(sellerBefore + PRICE.toNumber());
+    expect(await balance(buyer.publicKey)).to.equal(buyerBefore - PRICE.toNumber());
+    expect(await provider.connection.getAccountInfo(listing)).to.equal(null);
+    expect(await provider.connection.getAccountInfo(escrow)).to.equal(null);
+    const bought = await getAccount(
+      provider.connection,
+      getAssociatedTokenAddressSync(mint, buyer.publicKey)
+    );
+    expect(bought.amount).to.equal(BigInt(1));
+  });
+
+  it("fails a second buy once the listing has closed", async () => {
+    const { mint, seller } = await newSeller();
+    const listing = await listItem(seller, mint, PRICE);
+    await buy(listing, seller.publicKey, mint, await newBuyer(mint));
+
+    const late = await newBuyer(mint);
+    const before = await balance(late.publicKey);
+    await expectError(buy(listing, seller.publicKey, mint, late), "AccountNotInitialized");
+    expect(await balance(late.publicKey)).to.equal(before);
+  });
+
+  it("returns the item and the rent to a seller who delists", async () => {
+    const { mint, seller } = await newSeller();
+    const sellerBefore = await balance(seller.publicKey);
+    const listing = await listItem(seller, mint, PRICE);
+    const from = getAssociatedTokenAddressSync(mint, seller.publicKey);
+
+    await program.methods
+      .delistItem()
+      .accounts({ seller: seller.publicKey, listing, escrow: escrowPda(listing), to: from })
+      .signers([seller])
+      .rpc();
+    expect(await balance(seller.publicKey)).to.equal(sellerBefore);
+    expect(await provider.connection.getAccountInfo(escrowPda(listing))).to.equal(null);
+    expect((await getAccount(provider.connection, from)).amount).to.equal(BigInt(1));
+  });
+});
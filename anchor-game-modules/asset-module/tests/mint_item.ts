@@ -0,0 +1,109 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  createAssociatedTokenAccount,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  mintTo,
+} from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  admin,
+  createItem,
+  expectError,
+  itemArgs,
+  mintConfigPda,
+  mintItemAccounts,
+  mintItemTo,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 3;
+// SPL Token's OwnerMismatch, returned when the signer isn't the mint authority.
+const OWNER_MISMATCH = "0x4";
+
+describe("mint_item", () => {
+  let mint: PublicKey;
+
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+    mint = await createItem(itemArgs(TYPE_ID, { decimals: 2, maxSupply: new BN(1_000) }));
+  });
+
+  it("creates the recipient's token account when it's missing", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const ata = getAssociatedTokenAddressSync(mint, recipient);
+    expect(await provider.connection.getAccountInfo(ata)).to.equal(null);
+
+    await program.methods
+      .mintItem(new BN(10))
+      .accounts(mintItemAccounts(mint, TYPE_ID, recipient))
+      .rpc();
+    const account = await getAccount(provider.connection, ata);
+    expect(account.owner).to.deep.equal(recipient);
+    expect(account.amount).to.equal(BigInt(10));
+  });
+
+  it("mints into an existing token account", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+    const ata = await createAssociatedTokenAccount(provider.connection, payer, mint, recipient);
+
+    await program.methods
+      .mintItem(new BN(7))
+      .accounts(mintItemAccounts(mint, TYPE_ID, recipient))
+      .rpc();
+    await program.methods
+      .mintItem(new BN(3))
+      .accounts(mintItemAccounts(mint, TYPE_ID, recipient))
+      .rpc();
+    expect((await getAccount(provider.connection, ata)).amount).to.equal(BigInt(10));
+  });
+
+  it("rejects a token account that belongs to someone else", async () => {
+    const recipient = Keypair.generate().publicKey;
+    await program.methods
+      .mintItem(new BN(1))
+      .accounts(mintItemAccounts(mint, TYPE_ID, admin))
+      .rpc();
+    await expectError(
+      program.methods
+        .mintItem(new BN(1))
+        .accounts({
+          ...mintItemAccounts(mint, TYPE_ID, recipient),
+          to: getAssociatedTokenAddressSync(mint, admin),
+        })
+        .rpc(),
+      "ConstraintTokenOwner"
+    );
+  });
+
+  it("rejects a direct mint of a loot-only item", async () => {
+    const loot = await createItem(itemArgs(TYPE_ID, { lootOnly: true }));
+    const recipient = Keypair.generate().publicKey;
+    await expectError(
+      program.methods
+        .mintItem(new BN(1))
+        .accounts(mintItemAccounts(loot, TYPE_ID, recipient))
+        .rpc(),
+      "UnauthorizedMintPath"
+    );
+    const config = await program.account.mintConfig.fetch(mintConfigPda(loot));
+    expect(config.minted.toNumber()).to.equal(0);
+  });
+
+  it("only mints through the program, not a wallet's direct mint_to", async () => {
+    const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+    const recipient = Keypair.generate().publicKey;
+    const ata = await createAssociatedTokenAccount(provider.connection, payer, mint, recipient);
+
+    // The admin created the item, but the mint authority is the program's PDA.
+    await expectError(mintTo(provider.connection, payer, mint, ata, payer, 1), OWNER_MISMATCH);
+    expect((await getAccount(provider.connection, ata)).amount).to.equal(BigInt(0));
+
+    await mintItemTo(mint, TYPE_ID, recipient);
+    expect((await getAccount(provider.connection, ata)).amount).to.equal(BigInt(1));
+  });
+});
@@ -0,0 +1,65 @@
+import { expect } from "chai";
+import { getAssociatedTokenAddressSync, getMint } from "@solana/spl-token";
+import { Keypair, PublicKey, SYSVAR_SLOT_HASHES_PUBKEY } from "@solana/web3.js";
+import {
+  admin,
+  configPda,
+  ensureGame,
+  expectError,
+  fundedKeypair,
+  mintAuthorityPda,
+  pda,
+  program,
+  provider,
+} from "./helpers";
+
+const itemDataPda = (mint: PublicKey) => pda([Buffer.from("item_data"), mint.toBuffer()]);
+
+const mintNftAccounts = (mint: PublicKey, recipient: PublicKey, signer: PublicKey) => ({
+  admin: signer,
+  config: configPda,
+  mint,
+  mintAuthority: mintAuthorityPda,
+  recipient,
+  to: getAssociatedTokenAddressSync(mint, recipient),
+  dropTable: null,
+  itemData: itemDataPda(mint),
+  slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+});
+
+describe("mint_nft", () => {
+  before(ensureGame);
+
+  it("mints a single 0-decimal token without a drop table", async () => {
+    const mint = Keypair.generate();
+    const recipient = Keypair.generate().publicKey;
+
+    await program.methods
+      .mintNft(true)
+      .accounts(mintNftAccounts(mint.publicKey, recipient, admin))
+      .signers([mint])
+      .rpc();
+
+    const created = await getMint(provider.connection, mint.publicKey);
+    expect(created.decimals).to.equal(0);
+    expect(created.supply).to.equal(BigInt(1));
+    expect(created.mintAuthority).to.equal(null);
+    const itemData = await program.account.itemData.fetch(itemDataPda(mint.publicKey));
+    expect(itemData.rarity).to.equal(0);
+  });
+
+  it("only lets the admin mint", async () => {
+    const stranger = await fundedKeypair();
+    const mint = Keypair.generate();
+
+    await expectError(
+      program.methods
+        .mintNft(true)
+        .accounts(mintNftAccounts(mint.publicKey, stranger.publicKey, stranger.publicKey))
+        .signers([stranger, mint])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+    expect(await provider.connection.getAccountInfo(mint.publicKey)).to.equal(null);
+  });
+});
@@ -0,0 +1,124 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { createAssociatedTokenAccount, getAccount } from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  createItem,
+  escrowPda,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  mintItemTo,
+  pda,
+  program,
+  provider,
+  registerItemType,
+} from "./helpers";
+
+const TYPE_ID = 10;
+
+describe("offers", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+  const amountOf = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account)).amount);
+
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+  });
+
+  const tokenAccount = (mint: PublicKey, owner: PublicKey) =>
+    createAssociatedTokenAccount(provider.connection, payer, mint, owner);
+
+  // A fresh sender holding three items offers two of them to a fresh recipient.
+  async function setUp() {
+    const mint = await createItem(itemArgs(TYPE_ID));
+    const sender = await fundedKeypair();
+    const recipient = await fundedKeypair();
+    const from = await mintItemTo(mint, TYPE_ID, sender.publicKey, 3);
+    const to = await tokenAccount(mint, recipient.publicKey);
+    const offer = pda([
+      Buffer.from("offer"),
+      sender.publicKey.toBuffer(),
+      recipient.publicKey.toBuffer(),
+      mint.toBuffer(),
+    ]);
+    await program.methods
+      .offerItem(new BN(2))
+      .accounts({
+        sender: sender.publicKey,
+        recipient: recipient.publicKey,
+        mint,
+        offer,
+        escrow: escrowPda(offer),
+        from,
+      })
+      .signers([sender])
+      .rpc();
+    return { mint, sender, recipient, from, to, offer };
+  }
+
+  const accept = (offer: PublicKey, sender: PublicKey, recipient: Keypair, to: PublicKey) =>
+    program.methods
+      .acceptItem()
+      .accounts({ recipient: recipient.publicKey, sender, offer, escrow: escrowPda(offer), to })
+      .signers([recipient])
+      .rpc();
+
+  const cancel = (offer: PublicKey, sender: Keypair, to: PublicKey) =>
+    program.methods
+      .cancelOffer()
+      .accounts({ sender: sender.publicKey, offer, escrow: escrowPda(offer), to })
+      .signers([sender])
+      .rpc();
+
+  it("escrows the offered items", async () => {
+    const { from, offer } = await setUp();
+    expect(await amountOf(from)).to.equal(1);
+    expect(await amountOf(escrowPda(offer))).to.equal(2);
+  });
+
+  it("hands the items to the recipient on accept", async () => {
+    const { sender, recipient, from, to, offer } = await setUp();
+
+    await accept(offer, sender.publicKey, recipient, to);
+    expect(await amountOf(to)).to.equal(2);
+    expect(await amountOf(from)).to.equal(1);
+    expect(await program.account.offer.fetchNullable(offer)).to.be.null;
+    expect(await provider.connection.getAccountInfo(escrowPda(offer))).to.be.null;
+  });
+
+  it("rejects a third party accepting the offer", async () => {
+    const { mint, sender, to, offer } = await setUp();
+    const stranger = await fundedKeypair();
+    const strangerTo = await tokenAccount(mint, stranger.publicKey);
+
+    await expectError(
+      accept(offer, sender.publicKey, stranger, strangerTo),
+      "NotOfferRecipient"
+    );
+    expect(await amountOf(strangerTo)).to.equal(0);
+    expect(await amountOf(to)).to.equal(0);
+    expect(await amountOf(escrowPda(offer))).to.equal(2);
+  });
+
+  it("returns the items and the rent to the sender on cancel", async () => {
+    const { sender, from, offer } = await setUp();
+    const before = await provider.connection.getBalance(sender.publicKey);
+    const rent =
+      (await provider.connection.getBalance(offer)) +
+      (await provider.connection.getBalance(escrowPda(offer)));
+
+    await cancel(offer, sender, from);
+    expect(await amountOf(from)).to.equal(3);
+    expect(await provider.connection.getBalance(sender.publicKey)).to.equal(before + rent);
+    expect(await program.account.offer.fetchNullable(offer)).to.be.null;
+  });
+
+  it("only lets the sender cancel", async () => {
+    const { recipient, to, offer } = await setUp();
+
+    await expectError(cancel(offer, recipient, to), "NotOfferSender");
+    expect(await amountOf(escrowPda(offer))).to.equal(2);
+    expect(await program.account.offer.fetchNullable(offer)).to.not.be.null;
+  });
+});
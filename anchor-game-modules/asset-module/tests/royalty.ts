@@ -0,0 +1,159 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { createAssociatedTokenAccount, getAccount } from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import {
+  buyItemAccounts,
+  createItem,
+  fundedKeypair,
+  itemArgs,
+  listItem,
+  mintItemTo,
+  program,
+  provider,
+  registerItemType,
+  royaltyPda,
+} from "./helpers";
+
+const TYPE_ID = 4;
+const PRICE = new BN(LAMPORTS_PER_SOL);
+
+describe("sale royalties", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+  });
+
+  // Creates an item with the given royalty, lists one from a fresh seller at `price` and opens
+  // a token account for a fresh buyer. The provider wallet pays every fee, so the seller's,
+  // buyer's and royalty recipient's balances only move by what the sale pays them.
+  async function setUp(royaltyRecipient: PublicKey, royaltyBasisPoints: number, price = PRICE) {
+    const mint = await createItem(itemArgs(TYPE_ID, { royaltyRecipient, royaltyBasisPoints }));
+    const seller = await fundedKeypair();
+    await mintItemTo(mint, TYPE_ID, seller.publicKey);
+    // Taken before listing, since the seller puts up the listing and escrow rent.
+    const sellerBefore = await provider.connection.getBalance(seller.publicKey);
+    const listing = await listItem(seller, mint, price);
+    const buyer = await fundedKeypair();
+    const to = await createAssociatedTokenAccount(
+      provider.connection,
+      payer,
+      mint,
+      buyer.publicKey
+    );
+    return { mint, seller, sellerBefore, listing, buyer, to };
+  }
+
+  it("pays the royalty's cut of the sale price", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const { mint, seller, listing, buyer, to } = await setUp(recipient, 500);
+    const before = await provider.connection.getBalance(buyer.publicKey);
+
+    await program.methods
+      .buyItem()
+      .accounts(buyItemAccounts(listing, seller.publicKey, mint, buyer.publicKey, recipient))
+      .signers([buyer])
+      .rpc();
+    expect(await provider.connection.getBalance(recipient)).to.equal(LAMPORTS_PER_SOL / 20);
+    const after = await provider.connection.getBalance(buyer.publicKey);
+    expect(after).to.equal(before - LAMPORTS_PER_SOL);
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+
+  it("pays nothing extra with a 0 bps royalty", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const { mint, seller, listing, buyer, to } = await setUp(recipient, 0);
+    const before = await provider.connection.getBalance(buyer.publicKey);
+
+    await program.methods
+      .buyItem()
+      .accounts(buyItemAccounts(listing, seller.publicKey, mint, buyer.publicKey, null))
+      .signers([buyer])
+      .rpc();
+    expect(await provider.connection.getBalance(recipient)).to.equal(0);
+    const after = await provider.connection.getBalance(buyer.publicKey);
+    expect(after).to.equal(before - LAMPORTS_PER_SOL);
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+
+  it("splits the price exactly, leaving the rounding remainder with the seller", async () => {
+    const recipient = Keypair.generate().publicKey;
+    // 333 bps of 1,000,003 is 33,300.0999, which rounds down to 33,300.
+    const price = 1_000_003;
+    const { mint, seller, sellerBefore, listing, buyer } = await setUp(
+      recipient,
+      333,
+      new BN(price)
+    );
+    const buyerBefore = await provider.connection.getBalance(buyer.publicKey);
+
+    await program.methods
+      .buyItem()
+      .accounts(buyItemAccounts(listing, seller.publicKey, mint, buyer.publicKey, recipient))
+      .signers([buyer])
+      .rpc();
+    expect(await provider.connection.getBalance(recipient)).to.equal(33_300);
+    expect(await provider.connection.getBalance(seller.publicKey)).to.equal(
+      sellerBefore + price - 33_300
+    );
+    expect(await provider.connection.getBalance(buyer.publicKey)).to.equal(buyerBefore - price);
+  });
+});
+
+describe("transfer royalties", () => {
+  const payer = (provider.wallet as unknown as { payer: Keypair }).payer;
+
+  // Creates an item with the given royalty, mints one to a fresh sender and opens a token
+  // account for a fresh receiver. The provider wallet pays every fee.
+  async function setUp(royaltyRecipient: PublicKey, royaltyBasisPoints: number) {
+    const mint = await createItem(itemArgs(TYPE_ID, { royaltyRecipient, royaltyBasisPoints }));
+    const sender = await fundedKeypair();
+    const from = await mintItemTo(mint, TYPE_ID, sender.publicKey);
+    const receiver = Keypair.generate().publicKey;
+    const to = await createAssociatedTokenAccount(provider.connection, payer, mint, receiver);
+    return { mint, sender, from, to };
+  }
+
+  const transfer = (
+    sender: Keypair,
+    mint: PublicKey,
+    from: PublicKey,
+    to: PublicKey,
+    royaltyRecipient: PublicKey | null
+  ) =>
+    program.methods
+      .transferItem(new BN(1), PRICE)
+      .accounts({
+        authority: sender.publicKey,
+        from,
+        to,
+        royaltyConfig: royaltyPda(mint),
+        royaltyRecipient,
+      })
+      .signers([sender])
+      .rpc();
+
+  it("has the sender pay the royalty's cut of the declared price", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const { mint, sender, from, to } = await setUp(recipient, 500);
+    const before = await provider.connection.getBalance(sender.publicKey);
+
+    await transfer(sender, mint, from, to, recipient);
+    expect(await provider.connection.getBalance(recipient)).to.equal(LAMPORTS_PER_SOL / 20);
+    const after = await provider.connection.getBalance(sender.publicKey);
+    expect(before - after).to.equal(LAMPORTS_PER_SOL / 20);
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+
+  it("pays nothing extra with a 0 bps royalty", async () => {
+    const recipient = Keypair.generate().publicKey;
+    const { mint, sender, from, to } = await setUp(recipient, 0);
+    const before = await provider.connection.getBalance(sender.publicKey);
+
+    await transfer(sender, mint, from, to, null);
+    expect(await provider.connection.getBalance(recipient)).to.equal(0);
+    expect(await provider.connection.getBalance(sender.publicKey)).to.equal(before);
+    expect((await getAccount(provider.connection, to)).amount).to.equal(BigInt(1));
+  });
+});
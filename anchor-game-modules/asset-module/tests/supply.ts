@@ -0,0 +1,64 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { getAccount, getAssociatedTokenAddressSync } from "@solana/spl-token";
+import {
+  admin,
+  configPda,
+  createItem,
+  expectError,
+  fundedKeypair,
+  itemArgs,
+  mintConfigPda,
+  mintItemAccounts,
+  program,
+  provider,
+  registerItemType,
+  stateProgram,
+} from "./helpers";
+
+const TYPE_ID = 2;
+
+describe("supply cap", () => {
+  before(async () => {
+    await registerItemType(TYPE_ID, 1_000_000);
+  });
+
+  it("hands the mint config to state_module's game admin", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID, { decimals: 2, maxSupply: new BN(500) }));
+    const game = await stateProgram.account.gameConfig.fetch(configPda);
+    const mintConfig = await program.account.mintConfig.fetch(mintConfigPda(mint));
+    expect(mintConfig.authority).to.deep.equal(game.admin);
+
+    const stranger = await fundedKeypair();
+    const accounts = mintItemAccounts(mint, TYPE_ID, stranger.publicKey);
+    await expectError(
+      program.methods
+        .mintItem(new BN(1))
+        .accounts({ ...accounts, authority: stranger.publicKey })
+        .signers([stranger])
+        .rpc(),
+      "NotMintAuthority"
+    );
+  });
+
+  it("mints up to max_supply and no further", async () => {
+    const mint = await createItem(itemArgs(TYPE_ID, { decimals: 2, maxSupply: new BN(500) }));
+    const accounts = mintItemAccounts(mint, TYPE_ID, admin);
+
+    await expectError(
+      program.methods.mintItem(new BN(0)).accounts(accounts).rpc(),
+      "InvalidAmount"
+    );
+    await program.methods.mintItem(new BN(300)).accounts(accounts).rpc();
+    await program.methods.mintItem(new BN(200)).accounts(accounts).rpc();
+    await expectError(
+      program.methods.mintItem(new BN(1)).accounts(accounts).rpc(),
+      "SupplyExhausted"
+    );
+
+    const held = await getAccount(provider.connection, getAssociatedTokenAddressSync(mint, admin));
+    expect(held.amount).to.equal(BigInt(500));
+    const mintConfig = await program.account.mintConfig.fetch(mintConfigPda(mint));
+    expect(mintConfig.minted.toNumber()).to.equal(500);
+  });
+});
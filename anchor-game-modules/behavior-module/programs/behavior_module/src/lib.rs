@@ -1,33 +1,3221 @@
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
+use asset_module::program::AssetModule;
+use asset_module::ItemMetadata;
+use game_common::{PlayerError, PlayerState, COMBAT_AUTHORITY_SEED, PLAYER_VERSION};
+use state_module::program::StateModule;
+use state_module::{GameConfig, CONFIG_SEED};
+
+declare_id!("BehAviorModu1eDemo11111111111111111111111111");
+
+pub const MIN_DAMAGE: u8 = 1;
+// Chance out of 100 that an attack lands a critical hit, which multiplies its damage.
+pub const CRIT_CHANCE_PERCENT: u64 = 10;
+pub const CRIT_MULTIPLIER: u16 = 2;
+// Dodge chance grows one percent per DODGE_AGILITY_PER_PERCENT agility, up to MAX_DODGE_PERCENT.
+pub const DODGE_AGILITY_PER_PERCENT: u8 = 4;
+pub const MAX_DODGE_PERCENT: u8 = 40;
+// Seconds a defeated player must wait before respawning.
+pub const RESPAWN_COOLDOWN_SECS: i64 = 300;
+// Experience for a kill, per level of the defeated player.
+pub const KILL_XP_PER_LEVEL: u64 = 50;
+
+// Items the attacker receives on a kill, whether minted by drop_on_kill or taken from the
+// defender's staked loot.
+pub const LOOT_AMOUNT: u64 = 1;
+
+// Staked loot sits in a token account at [STAKE_ESCROW_SEED, combat account, mint] held by the
+// combat authority until unstake_loot returns it or a killing blow pays it out.
+pub const STAKE_ESCROW_SEED: &[u8] = b"stake_escrow";
+
+// drop_on_kill mints one of the items in the admin's table at [LOOT_TABLE_SEED], picked by
+// weight.
+pub const LOOT_TABLE_SEED: &[u8] = b"loot_table";
+pub const MAX_LOOT_ENTRIES: usize = 8;
+
+// Stamina spent per attack, regenerating one point every STAMINA_REGEN_SECS up to MAX_STAMINA.
+pub const MAX_STAMINA: u8 = 100;
+pub const ATTACK_STAMINA_COST: u8 = 10;
+pub const STAMINA_REGEN_SECS: i64 = 60;
+
+// Mana spent by abilities, regenerating one point every MANA_REGEN_SECS up to MAX_MANA.
+pub const MAX_MANA: u16 = 100;
+pub const MANA_REGEN_SECS: i64 = 30;
+pub const HEAL_MANA_COST: u16 = 20;
+
+// Poison deals POISON_DAMAGE_PER_STACK per stack every POISON_TICK_SECS until it expires.
+pub const POISON_TICK_SECS: i64 = 10;
+pub const POISON_DAMAGE_PER_STACK: u64 = 1;
+
+// Area attacks cost AOE_MANA_COST on top of an attack's stamina and hit at most MAX_AOE_TARGETS
+// players, which keeps them within the compute budget.
+pub const MAX_AOE_TARGETS: usize = 8;
+pub const AOE_MANA_COST: u16 = 30;
+
+// Skill ids are bit positions in CombatState::skills; see skill_mana_cost for the known ones.
+pub const SKILL_FIREBALL: u8 = 0;
+pub const FIREBALL_DAMAGE: u16 = 15;
+pub const FIREBALL_MANA_COST: u16 = 25;
+
+// Temporary modifiers held per player. An effect's magnitude is added to the matching stat.
+pub const MAX_EFFECTS: usize = 4;
+pub const EFFECT_ATTACK: u8 = 0;
+pub const EFFECT_DEFENSE: u8 = 1;
+
+// Seconds a challenger must wait for an accept before cancel_match can refund them.
+pub const MATCH_ACCEPT_TIMEOUT_SECS: i64 = 24 * 60 * 60;
+// Seconds after acceptance before an undecided match can be refunded to both sides.
+pub const MATCH_RESOLVE_TIMEOUT_SECS: i64 = 24 * 60 * 60;
+
+// Wagered matches live at [MATCH_SEED, challenger player, opponent player].
+pub const MATCH_SEED: &[u8] = b"match";
+
+// Combat state lives at [COMBAT_SEED, player account], one per state_module player.
+pub const COMBAT_SEED: &[u8] = b"combat";
+// Bump when CombatState's layout changes. New fields go last, after `version`. Version 6 kept
+// the layout but moved staked loot from a delegation into an escrow.
+pub const COMBAT_VERSION: u8 = 7;
 
-declare_id!("BehAv10rM0Du13D3m0111111111111111111111111111111");
+// Players waiting for a PvP opponent, in the single queue at [MATCH_QUEUE_SEED].
+pub const MATCH_QUEUE_SEED: &[u8] = b"match_queue";
+pub const MAX_QUEUE_LEN: usize = 16;
+
+// Turn-based duels live at [BATTLE_SEED, challenger player, opponent player].
+pub const BATTLE_SEED: &[u8] = b"battle";
+// Seconds each side has to take its turn before the other can claim the battle.
+pub const BATTLE_TURN_SECS: i64 = 5 * 60;
+
+// Item wear lives at [DURABILITY_SEED, player account, item mint].
+pub const DURABILITY_SEED: &[u8] = b"durability";
 
 #[program]
 pub mod behavior_module {
     use super::*;
-    pub fn attack(ctx: Context<Attack>, damage: u8) -> Result<()> {
-        let player = &mut ctx.accounts.player;
-        require!(player.hp > damage, CustomError::PlayerDefeated);
-        player.hp -= damage;
+    pub fn enter_combat(ctx: Context<EnterCombat>) -> Result<()> {
+        let combat = &mut ctx.accounts.combat;
+        combat.player = ctx.accounts.player.key();
+        combat.max_hp = ctx.accounts.config.base_hp;
+        combat.hp = combat.max_hp;
+        combat.max_stamina = MAX_STAMINA;
+        combat.stamina = MAX_STAMINA;
+        combat.max_mana = MAX_MANA;
+        combat.mana = MAX_MANA;
+        let now = Clock::get()?.unix_timestamp;
+        combat.last_stamina_update = now;
+        combat.last_mana_update = now;
+        combat.last_active = now;
+        combat.bump = *ctx.bumps.get("combat").unwrap();
+        combat.version = COMBAT_VERSION;
+        Ok(())
+    }
+
+    // Rewrites a combat account in the current layout. Accounts from before `version` stored
+    // hp and max_hp as single bytes and are read by hand; later layouts only append fields,
+    // which come back zeroed from the realloc and get any non-zero default set here.
+    // Already-migrated accounts are left alone.
+    pub fn migrate_combat(ctx: Context<MigrateCombat>) -> Result<()> {
+        let info = ctx.accounts.combat.to_account_info();
+        let legacy = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() > 8 && data[..8] == CombatState::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            if data.len() == LegacyCombatState::SPACE {
+                let legacy = LegacyCombatState::deserialize(&mut &data[8..])
+                    .map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+                Some(legacy.upgrade())
+            } else {
+                None
+            }
+        };
+
+        let rent = Rent::get()?.minimum_balance(CombatState::SPACE);
+        let balance = info.lamports();
+        if balance < rent {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.owner.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                rent - balance,
+            )?;
+        }
+        info.realloc(CombatState::SPACE.max(info.data_len()), true)?;
+        let mut combat = match legacy {
+            Some(combat) => combat,
+            None => CombatState::try_deserialize(&mut &info.try_borrow_data()?[..])?,
+        };
+        if combat.version == COMBAT_VERSION {
+            return Ok(());
+        }
+        if combat.version < 3 {
+            combat.max_mana = MAX_MANA;
+            combat.mana = MAX_MANA;
+            combat.last_mana_update = Clock::get()?.unix_timestamp;
+        }
+        if combat.version < 6 {
+            // The old stake was only a delegation on the owner's token account, which the
+            // owner can revoke themselves; the item never left their wallet.
+            combat.staked_loot = None;
+        }
+        combat.version = COMBAT_VERSION;
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data[..];
+        combat.try_serialize(&mut writer)
+    }
+
+    // Damage comes from the attacker's and defender's stats and the attacker's level, never from
+    // the caller.
+    // With `drop_on_kill`, a killing blow also mints loot to the attacker's wallet.
+    pub fn attack(ctx: Context<Attack>, drop_on_kill: bool) -> Result<()> {
+        // Checked before anything is written: with attacker == defender both combat accounts
+        // alias the same state, and alts under one wallet could farm kills.
+        require_keys_neq!(
+            ctx.accounts.attacker.key(),
+            ctx.accounts.defender.key(),
+            CustomError::CannotAttackSelf
+        );
+        require_keys_neq!(
+            ctx.accounts.attacker.owner,
+            ctx.accounts.defender.owner,
+            CustomError::SameOwnerAttack
+        );
+        require!(!ctx.accounts.attacker_combat.defeated, CustomError::AttackerDefeated);
+        require!(
+            !ctx.accounts.attacker_combat.in_battle && !ctx.accounts.defender_combat.in_battle,
+            CustomError::InBattle
+        );
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        ctx.accounts.attacker_combat.clear_expired_effects(clock.slot);
+        ctx.accounts.defender_combat.clear_expired_effects(clock.slot);
+        // Poison settles first. If it finishes either side the attack stops there, but still
+        // succeeds so the defeat is saved.
+        let attacker_poisoned = ctx.accounts.attacker_combat.tick_poison(now)?;
+        let defender_poisoned = ctx.accounts.defender_combat.tick_poison(now)?;
+        if attacker_poisoned || defender_poisoned {
+            return Ok(());
+        }
+        check_attack_cooldown(
+            ctx.accounts.attacker_combat.last_attack_ts,
+            now,
+            ctx.accounts.config.attack_cooldown_secs,
+        )?;
+        let attacker_combat = &mut ctx.accounts.attacker_combat;
+        attacker_combat.regen_stamina(now);
+        attacker_combat.stamina = attacker_combat
+            .stamina
+            .checked_sub(ATTACK_STAMINA_COST)
+            .ok_or(CustomError::NotEnoughStamina)?;
+        let attack_bonus = ctx
+            .accounts
+            .attacker_combat
+            .effect_total(EFFECT_ATTACK, clock.slot)
+            .saturating_add(weapon_bonus(ctx.accounts)?);
+        let attack_power = modified_stat(ctx.accounts.attacker.attack_power, attack_bonus);
+        let defense = modified_stat(
+            ctx.accounts.defender.defense,
+            ctx.accounts.defender_combat.effect_total(EFFECT_DEFENSE, clock.slot),
+        );
+        let base = effective_damage(
+            scaled_damage(stat_damage(attack_power, defense), ctx.accounts.attacker.level),
+            armor_rating(ctx.accounts)?,
+        );
+        let nonce = ctx.accounts.attacker_combat.attack_nonce;
+        ctx.accounts.attacker_combat.attack_nonce = nonce.wrapping_add(1);
+        let attacker_key = ctx.accounts.attacker.key();
+        let seed = roll_seed(&ctx.accounts.slot_hashes, &attacker_key, nonce)?;
+        let damage = crit_damage(u16::from(base), &seed);
+        let dodged = roll_dodge(&seed, dodge_chance(ctx.accounts.defender.agility));
+        // A guard absorbs half of the next hit, and attacking drops your own guard. A dodged
+        // attack or one the defender's armor soaks up entirely doesn't land, so the defender
+        // keeps their guard.
+        ctx.accounts.attacker_combat.guarding = false;
+        let defender_combat = &mut ctx.accounts.defender_combat;
+        let damage = if dodged {
+            emit!(DodgeEvent {
+                attacker: attacker_key,
+                defender: ctx.accounts.defender.key(),
+            });
+            0
+        } else if damage == 0 {
+            0
+        } else if defender_combat.guarding {
+            defender_combat.guarding = false;
+            (damage / 2).max(u16::from(MIN_DAMAGE))
+        } else {
+            damage
+        };
+        let attacker_key = ctx.accounts.attacker.key();
+        let killed =
+            apply_damage(&mut ctx.accounts.defender_combat, damage, now, Some(attacker_key))?;
+        ctx.accounts.attacker_combat.last_attack_ts = now;
+        ctx.accounts.attacker_combat.last_active = now;
+        if killed {
+            let xp = KILL_XP_PER_LEVEL
+                .checked_mul(ctx.accounts.defender.level as u64)
+                .ok_or(CustomError::ArithmeticOverflow)?;
+            let bump = [*ctx.bumps.get("combat_authority").unwrap()];
+            let signer_seeds: &[&[u8]] = &[COMBAT_AUTHORITY_SEED, &bump];
+            let signer = &[signer_seeds];
+            let award_ctx = CpiContext::new_with_signer(
+                ctx.accounts.state_program.to_account_info(),
+                state_module::cpi::accounts::AwardCombatExperience {
+                    player: ctx.accounts.attacker.to_account_info(),
+                    config: ctx.accounts.config.to_account_info(),
+                    leaderboard: ctx.accounts.leaderboard.to_account_info(),
+                    combat_authority: ctx.accounts.combat_authority.to_account_info(),
+                },
+                signer,
+            );
+            state_module::cpi::award_combat_experience(award_ctx, xp)?;
+            if let Some(staked) = ctx.accounts.defender_combat.staked_loot.take() {
+                transfer_staked_loot(ctx.accounts, staked, signer_seeds)?;
+            }
+            if drop_on_kill {
+                drop_loot(ctx.accounts, &seed, signer_seeds)?;
+            }
+            emit!(KillRewarded {
+                attacker: ctx.accounts.attacker.key(),
+                defender: ctx.accounts.defender.key(),
+                xp,
+            });
+        }
+        emit!(CombatEvent {
+            attacker: ctx.accounts.attacker.key(),
+            defender: ctx.accounts.defender.key(),
+            damage_dealt: damage,
+            defender_hp_after: ctx.accounts.defender_combat.hp,
+            fatal: killed,
+        });
+        Ok(())
+    }
+
+    pub fn defend(ctx: Context<Defend>) -> Result<()> {
+        let combat = &mut ctx.accounts.combat;
+        require!(!combat.defeated, CustomError::AlreadyDefeated);
+        require!(!combat.guarding, CustomError::AlreadyGuarding);
+        combat.guarding = true;
+        combat.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    // Moves one item from the owner's token account into the combat's escrow, which attack
+    // hands to whoever lands the killing blow.
+    pub fn stake_loot(ctx: Context<StakeLoot>) -> Result<()> {
+        let combat = &mut ctx.accounts.combat;
+        require!(!combat.defeated, CustomError::AlreadyDefeated);
+        require!(combat.staked_loot.is_none(), CustomError::LootAlreadyStaked);
+        combat.staked_loot = Some(ctx.accounts.escrow.key());
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.loot_token_account.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, LOOT_AMOUNT)
+    }
+
+    // Survivors can take their stake back; a defeated player's stake has already been paid out.
+    // The escrow is closed and its rent returned to the owner.
+    pub fn unstake_loot(ctx: Context<UnstakeLoot>) -> Result<()> {
+        require!(!ctx.accounts.combat.defeated, CustomError::AlreadyDefeated);
+        ctx.accounts.combat.staked_loot = None;
+        let bump = [*ctx.bumps.get("combat_authority").unwrap()];
+        let signer_seeds: &[&[u8]] = &[COMBAT_AUTHORITY_SEED, &bump];
+        let signer = &[signer_seeds];
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.escrow.to_account_info(),
+                to: ctx.accounts.loot_token_account.to_account_info(),
+                authority: ctx.accounts.combat_authority.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(transfer_ctx, ctx.accounts.escrow.amount)?;
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.combat_authority.to_account_info(),
+            },
+            signer,
+        );
+        token::close_account(close_ctx)
+    }
+
+    pub fn heal(ctx: Context<Heal>, amount: u16) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidHealAmount);
+        let combat = &mut ctx.accounts.combat;
+        require!(!combat.defeated, CustomError::CannotHealDefeated);
+        require!(!combat.in_battle, CustomError::InBattle);
+        let clock = Clock::get()?;
+        combat.clear_expired_effects(clock.slot);
+        // As in attack, a poison defeat ends the heal without failing it.
+        if combat.tick_poison(clock.unix_timestamp)? {
+            return Ok(());
+        }
+        combat.regen_stamina(clock.unix_timestamp);
+        combat.spend_mana(HEAL_MANA_COST, clock.unix_timestamp)?;
+        let healed = combat.restore_hp(amount);
+        combat.last_active = clock.unix_timestamp;
+        emit!(PlayerHealed {
+            player: combat.player,
+            amount: healed,
+            hp: combat.hp,
+        });
+        Ok(())
+    }
+
+    pub fn learn_skill(ctx: Context<LearnSkill>, skill_id: u8) -> Result<()> {
+        skill_mana_cost(skill_id)?;
+        let combat = &mut ctx.accounts.combat;
+        combat.skills |= skill_bit(skill_id);
+        combat.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    // remaining_accounts holds a (player, combat state) pair per target. Each target takes half
+    // the damage a plain attack would deal it, before crits; guards and dodges don't apply.
+    // Pairs that aren't current, live combat states of another wallet's players are skipped
+    // rather than failing the whole attack.
+    pub fn area_attack(ctx: Context<AreaAttack>) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_AOE_TARGETS * 2,
+            CustomError::TooManyTargets
+        );
+        require!(
+            ctx.remaining_accounts.len() % 2 == 0,
+            CustomError::AoeAccountsMismatch
+        );
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let attacker = &ctx.accounts.attacker;
+        let attacker_combat = &mut ctx.accounts.attacker_combat;
+        require!(!attacker_combat.defeated, CustomError::AttackerDefeated);
+        require!(!attacker_combat.in_battle, CustomError::InBattle);
+        attacker_combat.clear_expired_effects(clock.slot);
+        check_attack_cooldown(
+            attacker_combat.last_attack_ts,
+            now,
+            ctx.accounts.config.attack_cooldown_secs,
+        )?;
+        attacker_combat.regen_stamina(now);
+        attacker_combat.stamina = attacker_combat
+            .stamina
+            .checked_sub(ATTACK_STAMINA_COST)
+            .ok_or(CustomError::NotEnoughStamina)?;
+        attacker_combat.spend_mana(AOE_MANA_COST, now)?;
+        attacker_combat.guarding = false;
+        attacker_combat.last_attack_ts = now;
+        attacker_combat.last_active = now;
+        let attack_power = modified_stat(
+            attacker.attack_power,
+            attacker_combat.effect_total(EFFECT_ATTACK, clock.slot),
+        );
+        let attacker_combat_key = attacker_combat.key();
+        let mut hit: Vec<Pubkey> = Vec::with_capacity(MAX_AOE_TARGETS);
+        for pair in ctx.remaining_accounts.chunks(2) {
+            let (player_info, combat_info) = (&pair[0], &pair[1]);
+            if player_info.owner != &game_common::ID
+                || combat_info.owner != &crate::ID
+                || !combat_info.is_writable
+                || combat_info.key() == attacker_combat_key
+                || hit.contains(combat_info.key)
+            {
+                continue;
+            }
+            let target =
+                match PlayerState::try_deserialize(&mut &player_info.try_borrow_data()?[..]) {
+                    Ok(target) => target,
+                    Err(_) => continue,
+                };
+            if target.version != PLAYER_VERSION || target.owner == attacker.owner {
+                continue;
+            }
+            // The discriminator check means only enter_combat's PDAs get through, and the
+            // player check ties the combat state to the target whose defense is used.
+            let mut combat =
+                match CombatState::try_deserialize(&mut &combat_info.try_borrow_data()?[..]) {
+                    Ok(combat) => combat,
+                    Err(_) => continue,
+                };
+            if combat.player != player_info.key()
+                || combat.version != COMBAT_VERSION
+                || combat.defeated
+                || combat.in_battle
+            {
+                continue;
+            }
+            combat.clear_expired_effects(clock.slot);
+            let defense =
+                modified_stat(target.defense, combat.effect_total(EFFECT_DEFENSE, clock.slot));
+            let base = scaled_damage(stat_damage(attack_power, defense), attacker.level);
+            let damage = u16::from((base / 2).max(MIN_DAMAGE));
+            let killed = apply_damage(&mut combat, damage, now, Some(attacker.key()))?;
+            {
+                let mut data = combat_info.try_borrow_mut_data()?;
+                let mut writer: &mut [u8] = &mut data[..];
+                combat.try_serialize(&mut writer)?;
+            }
+            hit.push(combat_info.key());
+            emit!(CombatEvent {
+                attacker: attacker.key(),
+                defender: combat.player,
+                damage_dealt: damage,
+                defender_hp_after: combat.hp,
+                fatal: killed,
+            });
+        }
+        Ok(())
+    }
+
+    // Mana is spent before the skill takes effect. Kills from skills don't award experience.
+    pub fn cast_skill(ctx: Context<CastSkill>, skill_id: u8) -> Result<()> {
+        let cost = skill_mana_cost(skill_id)?;
+        require_keys_neq!(
+            ctx.accounts.caster.key(),
+            ctx.accounts.target.key(),
+            CustomError::CannotAttackSelf
+        );
+        require_keys_neq!(
+            ctx.accounts.caster.owner,
+            ctx.accounts.target.owner,
+            CustomError::SameOwnerAttack
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let caster_combat = &mut ctx.accounts.caster_combat;
+        require!(!caster_combat.defeated, CustomError::AttackerDefeated);
+        require!(caster_combat.skills & skill_bit(skill_id) != 0, CustomError::SkillNotLearned);
+        require!(
+            !caster_combat.in_battle && !ctx.accounts.target_combat.in_battle,
+            CustomError::InBattle
+        );
+        caster_combat.spend_mana(cost, now)?;
+        caster_combat.last_active = now;
+        let damage = match skill_id {
+            SKILL_FIREBALL => FIREBALL_DAMAGE,
+            _ => return err!(CustomError::UnknownSkill),
+        };
+        let caster = ctx.accounts.caster.key();
+        let killed = apply_damage(&mut ctx.accounts.target_combat, damage, now, Some(caster))?;
+        emit!(CombatEvent {
+            attacker: ctx.accounts.caster.key(),
+            defender: ctx.accounts.target.key(),
+            damage_dealt: damage,
+            defender_hp_after: ctx.accounts.target_combat.hp,
+            fatal: killed,
+        });
+        Ok(())
+    }
+
+    pub fn respawn(ctx: Context<Respawn>) -> Result<()> {
+        let combat = &mut ctx.accounts.combat;
+        require!(combat.defeated, CustomError::NotDefeated);
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let ready_at = respawn_ready_at(combat.defeated_at);
+        if now < ready_at {
+            msg!("Respawn available in {} seconds", ready_at - now);
+            return err!(CustomError::RespawnCooldownActive);
+        }
+        combat.clear_expired_effects(clock.slot);
+        combat.regen_stamina(now);
+        combat.revive(now);
+        emit!(PlayerRespawned {
+            player: combat.player,
+            hp: combat.hp,
+            death_count: combat.death_count,
+        });
         Ok(())
     }
+
+    pub fn create_match(ctx: Context<CreateMatch>, wager_lamports: u64) -> Result<()> {
+        require!(wager_lamports > 0, CustomError::InvalidWager);
+        let match_account = &mut ctx.accounts.match_account;
+        match_account.challenger = ctx.accounts.challenger.key();
+        match_account.challenger_owner = ctx.accounts.owner.key();
+        match_account.opponent = ctx.accounts.opponent.key();
+        match_account.wager = wager_lamports;
+        match_account.created_at = Clock::get()?.unix_timestamp;
+        match_account.bump = *ctx.bumps.get("match_account").unwrap();
+        escrow_wager(
+            &ctx.accounts.system_program,
+            &ctx.accounts.owner,
+            &ctx.accounts.match_account,
+            wager_lamports,
+        )
+    }
+
+    pub fn accept_match(ctx: Context<AcceptMatch>) -> Result<()> {
+        let match_account = &mut ctx.accounts.match_account;
+        match_account.accepted = true;
+        match_account.accepted_at = Clock::get()?.unix_timestamp;
+        match_account.opponent_owner = ctx.accounts.owner.key();
+        let wager = match_account.wager;
+        escrow_wager(
+            &ctx.accounts.system_program,
+            &ctx.accounts.owner,
+            &ctx.accounts.match_account,
+            wager,
+        )
+    }
+
+    // Pays both wagers to the side that didn't fall first after acceptance; the account's rent
+    // goes back to the challenger when it closes. Closing also makes a second resolution
+    // impossible.
+    pub fn resolve_match(ctx: Context<ResolveMatch>) -> Result<()> {
+        let match_account = &ctx.accounts.match_account;
+        let (winner, winner_owner) = match_account
+            .winner(&ctx.accounts.challenger_combat, &ctx.accounts.opponent_combat)
+            .ok_or(CustomError::MatchNotConcluded)?;
+        require_keys_eq!(ctx.accounts.winner.key(), winner_owner, CustomError::WrongMatchWinner);
+        let pot = match_account
+            .wager
+            .checked_mul(2)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        **ctx.accounts.match_account.to_account_info().try_borrow_mut_lamports()? -= pot;
+        **ctx.accounts.winner.try_borrow_mut_lamports()? += pot;
+        emit!(MatchResolved {
+            match_account: ctx.accounts.match_account.key(),
+            winner,
+            pot,
+        });
+        Ok(())
+    }
+
+    // Closing the account refunds the challenger's stake along with its rent.
+    pub fn cancel_match(ctx: Context<CancelMatch>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now.saturating_sub(ctx.accounts.match_account.created_at) >= MATCH_ACCEPT_TIMEOUT_SECS,
+            CustomError::MatchNotExpired
+        );
+        Ok(())
+    }
+
+    // Anyone may call this once an accepted match has gone undecided past its deadline. Each
+    // side gets its wager back; the challenger also gets the rent when the account closes.
+    pub fn refund_match(ctx: Context<RefundMatch>) -> Result<()> {
+        let match_account = &ctx.accounts.match_account;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now.saturating_sub(match_account.accepted_at) >= MATCH_RESOLVE_TIMEOUT_SECS,
+            CustomError::MatchDeadlineNotReached
+        );
+        require!(
+            match_account
+                .winner(&ctx.accounts.challenger_combat, &ctx.accounts.opponent_combat)
+                .is_none(),
+            CustomError::MatchConcluded
+        );
+        let wager = match_account.wager;
+        **ctx.accounts.match_account.to_account_info().try_borrow_mut_lamports()? -= wager;
+        **ctx.accounts.opponent_owner.try_borrow_mut_lamports()? += wager;
+        Ok(())
+    }
+
+    // Admin-only. Replaces the whole table; entries with zero weight never drop.
+    pub fn set_loot_table(ctx: Context<SetLootTable>, entries: Vec<LootEntry>) -> Result<()> {
+        require!(
+            entries.len() <= MAX_LOOT_ENTRIES && entries.iter().any(|entry| entry.weight > 0),
+            CustomError::InvalidLootTable
+        );
+        let loot_table = &mut ctx.accounts.loot_table;
+        loot_table.entries = entries;
+        loot_table.bump = *ctx.bumps.get("loot_table").unwrap();
+        Ok(())
+    }
+
+    // Admin-only, once; the queue is shared by every player.
+    pub fn init_match_queue(ctx: Context<InitMatchQueue>) -> Result<()> {
+        ctx.accounts.queue.bump = *ctx.bumps.get("queue").unwrap();
+        Ok(())
+    }
+
+    pub fn enqueue(ctx: Context<Enqueue>) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let queue = &mut ctx.accounts.queue;
+        require!(queue.position(&player.key()).is_none(), CustomError::AlreadyQueued);
+        let len = queue.len as usize;
+        require!(len < MAX_QUEUE_LEN, CustomError::QueueFull);
+        queue.entries[len] = QueueEntry {
+            player: player.key(),
+            level: player.level,
+        };
+        queue.len += 1;
+        Ok(())
+    }
+
+    // Pairs the caller with the waiting player closest to their level, earliest queued on a
+    // tie, and takes both out of the queue. The caller need not be queued themselves.
+    pub fn dequeue_match(ctx: Context<DequeueMatch>) -> Result<Pubkey> {
+        let player = &ctx.accounts.player;
+        let queue = &mut ctx.accounts.queue;
+        let opponent = queue
+            .closest(&player.key(), player.level)
+            .ok_or(CustomError::QueueEmpty)?;
+        queue.remove(&opponent);
+        queue.remove(&player.key());
+        emit!(MatchFound {
+            player: player.key(),
+            opponent,
+        });
+        Ok(opponent)
+    }
+
+    pub fn challenge(ctx: Context<Challenge>) -> Result<()> {
+        require!(
+            !ctx.accounts.challenger_combat.in_battle && !ctx.accounts.opponent_combat.in_battle,
+            CustomError::InBattle
+        );
+        let battle = &mut ctx.accounts.battle;
+        battle.challenger = ctx.accounts.challenger.key();
+        battle.challenger_owner = ctx.accounts.owner.key();
+        battle.opponent = ctx.accounts.opponent.key();
+        battle.status = BattleStatus::Challenged;
+        battle.bump = *ctx.bumps.get("battle").unwrap();
+        Ok(())
+    }
+
+    // Locks both players into the battle; the challenger moves first.
+    pub fn accept_challenge(mut ctx: Context<AcceptChallenge>) -> Result<()> {
+        let bump = *ctx.bumps.get("combat_authority").unwrap();
+        let accounts = &mut ctx.accounts;
+        require_keys_eq!(
+            accounts.opponent.owner,
+            accounts.owner.key(),
+            CustomError::NotBattleParticipant
+        );
+        require!(accounts.battle.status == BattleStatus::Challenged, CustomError::BattleNotPending);
+        for combat in [&accounts.challenger_combat, &accounts.opponent_combat] {
+            require!(!combat.defeated, CustomError::AlreadyDefeated);
+            require!(!combat.in_battle, CustomError::InBattle);
+        }
+        accounts.challenger_combat.in_battle = true;
+        accounts.opponent_combat.in_battle = true;
+        set_players_locked(
+            &accounts.state_program,
+            &accounts.combat_authority,
+            [&accounts.challenger, &accounts.opponent],
+            true,
+            bump,
+        )?;
+        let battle = &mut accounts.battle;
+        battle.status = BattleStatus::Active;
+        battle.turn = battle.challenger;
+        battle.turn_deadline = Clock::get()?
+            .unix_timestamp
+            .saturating_add(BATTLE_TURN_SECS);
+        Ok(())
+    }
+
+    // Only the player whose turn it is may act, and only before the turn deadline. Battle
+    // attacks use stats, effects and level like attack, without crits, stamina or weapons.
+    pub fn take_turn(mut ctx: Context<BattleTurn>, action: BattleAction) -> Result<()> {
+        let clock = Clock::get()?;
+        let bump = *ctx.bumps.get("combat_authority").unwrap();
+        let accounts = &mut ctx.accounts;
+        require!(accounts.battle.status == BattleStatus::Active, CustomError::BattleNotActive);
+        // Like attack, a poison defeat ends the battle without failing the turn.
+        if settle_battle_poison(accounts, clock.unix_timestamp, bump)? {
+            return Ok(());
+        }
+        require!(clock.unix_timestamp <= accounts.battle.turn_deadline, CustomError::TurnExpired);
+        let challenger_turn = accounts.battle.turn == accounts.challenger.key();
+        let (actor, actor_combat, foe, foe_combat) = if challenger_turn {
+            (
+                &accounts.challenger,
+                &mut accounts.challenger_combat,
+                &accounts.opponent,
+                &mut accounts.opponent_combat,
+            )
+        } else {
+            (
+                &accounts.opponent,
+                &mut accounts.opponent_combat,
+                &accounts.challenger,
+                &mut accounts.challenger_combat,
+            )
+        };
+        require_keys_eq!(actor.owner, accounts.owner.key(), CustomError::NotYourTurn);
+        actor_combat.clear_expired_effects(clock.slot);
+        foe_combat.clear_expired_effects(clock.slot);
+        actor_combat.last_active = clock.unix_timestamp;
+        let mut winner = None;
+        match action {
+            BattleAction::Attack => {
+                actor_combat.guarding = false;
+                let attack_power = modified_stat(
+                    actor.attack_power,
+                    actor_combat.effect_total(EFFECT_ATTACK, clock.slot),
+                );
+                let defense = modified_stat(
+                    foe.defense,
+                    foe_combat.effect_total(EFFECT_DEFENSE, clock.slot),
+                );
+                let base = scaled_damage(stat_damage(attack_power, defense), actor.level);
+                let mut damage = u16::from(base);
+                if foe_combat.guarding {
+                    foe_combat.guarding = false;
+                    damage = (damage / 2).max(u16::from(MIN_DAMAGE));
+                }
+                if apply_damage(foe_combat, damage, clock.unix_timestamp, Some(actor.key()))? {
+                    winner = Some(actor.key());
+                }
+                emit!(CombatEvent {
+                    attacker: actor.key(),
+                    defender: foe.key(),
+                    damage_dealt: damage,
+                    defender_hp_after: foe_combat.hp,
+                    fatal: winner.is_some(),
+                });
+            }
+            BattleAction::Defend => actor_combat.guarding = true,
+        }
+        let next_turn = foe.key();
+        match winner {
+            Some(winner) => finish_battle(accounts, Some(winner), bump)?,
+            None => {
+                accounts.battle.turn = next_turn;
+                accounts.battle.turn_deadline =
+                    clock.unix_timestamp.saturating_add(BATTLE_TURN_SECS);
+            }
+        }
+        Ok(())
+    }
+
+    // Either side can walk away. Before the challenge is accepted there is no winner.
+    pub fn forfeit(mut ctx: Context<BattleTurn>) -> Result<()> {
+        let bump = *ctx.bumps.get("combat_authority").unwrap();
+        let accounts = &mut ctx.accounts;
+        let signer = accounts.owner.key();
+        let winner = if signer == accounts.challenger.owner {
+            accounts.battle.opponent
+        } else if signer == accounts.opponent.owner {
+            accounts.battle.challenger
+        } else {
+            return err!(CustomError::NotBattleParticipant);
+        };
+        match accounts.battle.status {
+            // Neither side was locked in yet. Either may be in some other battle, so in_battle
+            // is left alone.
+            BattleStatus::Challenged => {
+                accounts.battle.status = BattleStatus::Finished;
+                emit!(BattleFinished {
+                    battle: accounts.battle.key(),
+                    winner: None,
+                });
+            }
+            BattleStatus::Active => finish_battle(accounts, Some(winner), bump)?,
+            BattleStatus::Finished => return err!(CustomError::BattleNotActive),
+        }
+        Ok(())
+    }
+
+    // The player waiting on an opponent who let the turn deadline pass wins the battle.
+    pub fn claim_timeout_victory(mut ctx: Context<BattleTurn>) -> Result<()> {
+        let bump = *ctx.bumps.get("combat_authority").unwrap();
+        let accounts = &mut ctx.accounts;
+        require!(accounts.battle.status == BattleStatus::Active, CustomError::BattleNotActive);
+        let now = Clock::get()?.unix_timestamp;
+        if settle_battle_poison(accounts, now, bump)? {
+            return Ok(());
+        }
+        require!(now > accounts.battle.turn_deadline, CustomError::TurnNotExpired);
+        let waiting = if accounts.battle.turn == accounts.challenger.key() {
+            &accounts.opponent
+        } else {
+            &accounts.challenger
+        };
+        require_keys_eq!(waiting.owner, accounts.owner.key(), CustomError::NotBattleParticipant);
+        let winner = waiting.key();
+        finish_battle(accounts, Some(winner), bump)
+    }
+
+    // Finished battles are closed so the same pair can battle again.
+    pub fn close_battle(_ctx: Context<CloseBattle>) -> Result<()> {
+        Ok(())
+    }
+
+    // Admin-only. Starts tracking wear on one player's copy of an item. With `burn_on_break`,
+    // the use that wears the item out also burns it.
+    pub fn init_durability(
+        ctx: Context<InitDurability>,
+        max: u16,
+        burn_on_break: bool,
+    ) -> Result<()> {
+        require!(max > 0, CustomError::InvalidDurability);
+        let durability = &mut ctx.accounts.durability;
+        durability.player = ctx.accounts.player.key();
+        durability.mint = ctx.accounts.mint.key();
+        durability.max = max;
+        durability.current = max;
+        durability.burn_on_break = burn_on_break;
+        durability.bump = *ctx.bumps.get("durability").unwrap();
+        Ok(())
+    }
+
+    // Each use costs one point. The use that reaches zero still succeeds; any later use fails.
+    pub fn use_item(ctx: Context<UseItem>) -> Result<()> {
+        let durability = &mut ctx.accounts.durability;
+        require!(durability.current > 0, CustomError::ItemBroken);
+        durability.current -= 1;
+        if durability.current > 0 {
+            return Ok(());
+        }
+        let burned = durability.burn_on_break;
+        if burned {
+            burn_worn_item(ctx.accounts)?;
+        }
+        emit!(ItemWornOut {
+            player: ctx.accounts.player.key(),
+            mint: ctx.accounts.durability.mint,
+            burned,
+        });
+        Ok(())
+    }
+
+    // Admin-only. Stacks add to any poison already running, and the expiry is reset to
+    // `duration_secs` from now.
+    pub fn apply_poison(ctx: Context<ApplyEffect>, stacks: u8, duration_secs: i64) -> Result<()> {
+        require!(stacks > 0 && duration_secs > 0, CustomError::InvalidEffect);
+        let now = Clock::get()?.unix_timestamp;
+        let combat = &mut ctx.accounts.combat;
+        require!(!combat.defeated, CustomError::AlreadyDefeated);
+        if combat.tick_poison(now)? {
+            return Ok(());
+        }
+        if combat.poison_stacks == 0 {
+            combat.last_poison_tick = now;
+        }
+        combat.poison_stacks = combat.poison_stacks.saturating_add(stacks);
+        combat.poison_until_ts = now.saturating_add(duration_secs);
+        Ok(())
+    }
+
+    // Permissionless crank, so poison lands on idle players too. Damage only accrues per
+    // full tick since last_poison_tick, so cranking more often never speeds it up.
+    pub fn resolve_dots(ctx: Context<ResolveDots>) -> Result<()> {
+        let clock = Clock::get()?;
+        let combat = &mut ctx.accounts.combat;
+        combat.clear_expired_effects(clock.slot);
+        combat.tick_poison(clock.unix_timestamp)?;
+        Ok(())
+    }
+
+    // Admin-only. Fills a free effect slot, after clearing any that have expired.
+    pub fn apply_effect(
+        ctx: Context<ApplyEffect>,
+        kind: u8,
+        magnitude: i16,
+        duration_slots: u64,
+    ) -> Result<()> {
+        require!(kind <= EFFECT_DEFENSE, CustomError::InvalidEffect);
+        require!(duration_slots > 0, CustomError::InvalidEffect);
+        let slot = Clock::get()?.slot;
+        let combat = &mut ctx.accounts.combat;
+        combat.clear_expired_effects(slot);
+        let expires_at_slot = slot
+            .checked_add(duration_slots)
+            .ok_or(CustomError::ArithmeticOverflow)?;
+        let free = combat
+            .effects
+            .iter_mut()
+            .find(|effect| !effect.is_active(slot))
+            .ok_or(CustomError::EffectSlotsFull)?;
+        *free = ActiveEffect {
+            kind,
+            magnitude,
+            expires_at_slot,
+        };
+        Ok(())
+    }
+}
+
+pub fn modified_stat(base: u8, modifier: i16) -> u8 {
+    (base as i16).saturating_add(modifier).clamp(0, u8::MAX as i16) as u8
+}
+
+// Defense absorbs attack point for point, but every hit deals at least MIN_DAMAGE.
+pub fn stat_damage(attack_power: u8, defense: u8) -> u8 {
+    attack_power.saturating_sub(defense).max(MIN_DAMAGE)
+}
+
+// Armor soaks up damage point for point after defense and its MIN_DAMAGE floor, so unlike
+// defense it can stop a weak hit entirely.
+pub fn effective_damage(damage: u8, armor: u8) -> u8 {
+    damage.saturating_sub(armor)
+}
+
+// Higher-level attackers add half their level on top of the stat-derived damage.
+pub fn scaled_damage(base: u8, level: u8) -> u8 {
+    base.saturating_add(level / 2)
+}
+
+// A blow that takes hp to zero defeats the player; a defeated player cannot be hit again.
+// `by` is the attacking player, or None for poison. Returns whether this blow was the killing one.
+fn apply_damage(
+    combat: &mut CombatState,
+    damage: u16,
+    now: i64,
+    by: Option<Pubkey>,
+) -> Result<bool> {
+    require!(!combat.defeated, CustomError::AlreadyDefeated);
+    // Overkill is expected; hp floors at zero rather than erroring.
+    combat.hp = combat.hp.saturating_sub(damage);
+    if combat.hp == 0 {
+        combat.defeated = true;
+        combat.defeated_at = now;
+        combat.defeated_by = by;
+        emit!(PlayerDefeated {
+            player: combat.player,
+        });
+    }
+    Ok(combat.defeated)
+}
+
+// Seeds an attack's rolls with the most recent slot hash, the attacking player and their attack
+// nonce. Slot hashes are public before the transaction lands and a leader can influence them,
+// and the nonce is readable on-chain, so a determined player can simulate a roll and only send
+// winning attacks. This keeps casual players from predicting rolls; it is not safe for anything
+// of value.
+fn roll_seed(slot_hashes: &AccountInfo, player: &Pubkey, nonce: u64) -> Result<Vec<u8>> {
+    let data = slot_hashes.try_borrow_data()?;
+    // Layout: u64 entry count, then (u64 slot, [u8; 32] hash) entries, newest first.
+    let recent_hash = data.get(16..48).ok_or(CustomError::SlotHashesUnavailable)?;
+    Ok([recent_hash, player.as_ref(), &nonce.to_le_bytes()[..]].concat())
+}
+
+fn crit_damage(damage: u16, seed: &[u8]) -> u16 {
+    if roll_crit(seed) {
+        // Crit damage caps at u16::MAX, which already kills any player.
+        damage.saturating_mul(CRIT_MULTIPLIER)
+    } else {
+        damage
+    }
+}
+
+// Crits land with probability CRIT_CHANCE_PERCENT / 100 over uniformly random seeds.
+pub fn roll_crit(seed: &[u8]) -> bool {
+    let digest = hash(seed).to_bytes();
+    let roll = u64::from_le_bytes(digest[..8].try_into().unwrap()) % 100;
+    roll < CRIT_CHANCE_PERCENT
+}
+
+// Percent chance to dodge; non-decreasing in agility and capped at MAX_DODGE_PERCENT.
+pub fn dodge_chance(agility: u8) -> u8 {
+    (agility / DODGE_AGILITY_PER_PERCENT).min(MAX_DODGE_PERCENT)
+}
+
+// Picks an entry's mint with probability proportional to its weight over uniformly random
+// seeds; None if every weight is zero. Reads different digest bytes than roll_crit and
+// roll_dodge so the rolls are independent.
+pub fn pick_loot(seed: &[u8], entries: &[LootEntry]) -> Option<Pubkey> {
+    let total: u64 = entries.iter().map(|entry| entry.weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let digest = hash(seed).to_bytes();
+    let mut roll = u64::from_le_bytes(digest[16..24].try_into().unwrap()) % total;
+    for entry in entries {
+        if roll < entry.weight as u64 {
+            return Some(entry.mint);
+        }
+        roll -= entry.weight as u64;
+    }
+    None
+}
+
+// Reads different digest bytes than roll_crit so the two rolls are independent.
+pub fn roll_dodge(seed: &[u8], chance_percent: u8) -> bool {
+    let digest = hash(seed).to_bytes();
+    let roll = u64::from_le_bytes(digest[8..16].try_into().unwrap()) % 100;
+    roll < chance_percent as u64
+}
+
+// Poison keeps ticking mid-battle, and resolve_dots can land it between turns. Ticks both sides
+// and finishes the battle if either is down. Returns whether it did.
+fn settle_battle_poison(accounts: &mut BattleTurn, now: i64, bump: u8) -> Result<bool> {
+    accounts.challenger_combat.tick_poison(now)?;
+    accounts.opponent_combat.tick_poison(now)?;
+    let winner = battle_survivor(
+        (accounts.challenger.key(), &accounts.challenger_combat),
+        (accounts.opponent.key(), &accounts.opponent_combat),
+    );
+    match winner {
+        Some(winner) => finish_battle(accounts, winner, bump).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+// None while both sides stand. Otherwise the battle is over and the one still standing won,
+// with no winner after a double knockout.
+fn battle_survivor(
+    challenger: (Pubkey, &CombatState),
+    opponent: (Pubkey, &CombatState),
+) -> Option<Option<Pubkey>> {
+    match (challenger.1.defeated, opponent.1.defeated) {
+        (false, false) => None,
+        (false, true) => Some(Some(challenger.0)),
+        (true, false) => Some(Some(opponent.0)),
+        (true, true) => Some(None),
+    }
+}
+
+fn finish_battle(accounts: &mut BattleTurn, winner: Option<Pubkey>, bump: u8) -> Result<()> {
+    accounts.challenger_combat.in_battle = false;
+    accounts.opponent_combat.in_battle = false;
+    set_players_locked(
+        &accounts.state_program,
+        &accounts.combat_authority,
+        [&accounts.challenger, &accounts.opponent],
+        false,
+        bump,
+    )?;
+    accounts.battle.status = BattleStatus::Finished;
+    accounts.battle.winner = winner;
+    emit!(BattleFinished {
+        battle: accounts.battle.key(),
+        winner,
+    });
+    Ok(())
+}
+
+// Mirrors in_battle onto state_module's PlayerState, where it keeps close_player from closing
+// a player mid-battle.
+fn set_players_locked<'info>(
+    state_program: &Program<'info, StateModule>,
+    combat_authority: &UncheckedAccount<'info>,
+    players: [&Account<'info, PlayerState>; 2],
+    locked: bool,
+    bump: u8,
+) -> Result<()> {
+    let bump = [bump];
+    let signer_seeds: &[&[u8]] = &[COMBAT_AUTHORITY_SEED, &bump];
+    let signer = &[signer_seeds];
+    for player in players {
+        let lock_ctx = CpiContext::new_with_signer(
+            state_program.to_account_info(),
+            state_module::cpi::accounts::SetPlayerLocked {
+                player: player.to_account_info(),
+                combat_authority: combat_authority.to_account_info(),
+            },
+            signer,
+        );
+        state_module::cpi::set_player_locked(lock_ctx, locked)?;
+    }
+    Ok(())
+}
+
+// Applies one point of regeneration per `regen_secs` since `last_update`, capped at `max`.
+// Leftover seconds toward the next point carry over; u64 math keeps arbitrarily long gaps from
+// overflowing.
+fn regenerate(current: u64, max: u64, last_update: &mut i64, now: i64, regen_secs: i64) -> u64 {
+    let elapsed = now.saturating_sub(*last_update).max(0) as u64;
+    let points = elapsed / regen_secs as u64;
+    let value = current.saturating_add(points);
+    if value >= max {
+        *last_update = now;
+        max
+    } else {
+        *last_update += (points * regen_secs as u64) as i64;
+        value
+    }
+}
+
+// A skill's bit in CombatState::skills; ids past the mask's width have none.
+fn skill_bit(skill_id: u8) -> u32 {
+    1u32.checked_shl(u32::from(skill_id)).unwrap_or(0)
+}
+
+// Add new skills here; learn_skill and cast_skill reject any id without an entry.
+fn skill_mana_cost(skill_id: u8) -> Result<u16> {
+    match skill_id {
+        SKILL_FIREBALL => Ok(FIREBALL_MANA_COST),
+        _ => err!(CustomError::UnknownSkill),
+    }
+}
+
+fn escrow_wager<'info>(
+    system_program: &Program<'info, System>,
+    from: &Signer<'info>,
+    match_account: &Account<'info, Match>,
+    lamports: u64,
+) -> Result<()> {
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: from.to_account_info(),
+                to: match_account.to_account_info(),
+            },
+        ),
+        lamports,
+    )
+}
+
+// The weapon accounts are optional; without them the attacker fights on base stats. With them,
+// the item must be the attacker's equipped weapon and held in the signing wallet.
+fn weapon_bonus(accounts: &Attack) -> Result<i16> {
+    let weapon = (&accounts.weapon_metadata, &accounts.weapon_token_account);
+    let (metadata, token_account) = match weapon {
+        (None, None) => return Ok(0),
+        (Some(metadata), Some(token_account)) => (metadata, token_account),
+        _ => return err!(CustomError::WeaponAccountsIncomplete),
+    };
+    require_keys_eq!(token_account.mint, metadata.mint, CustomError::WeaponMismatch);
+    require_keys_eq!(token_account.owner, accounts.owner.key(), CustomError::WeaponNotHeld);
+    require!(token_account.amount >= 1, CustomError::WeaponNotHeld);
+    require!(
+        accounts.attacker.equipment.weapon == Some(metadata.mint),
+        CustomError::WeaponNotEquipped
+    );
+    Ok(metadata.stat_bonus)
+}
+
+// The defender's equipped armor, whose metadata and token account the attacker must pass so it
+// can't be left out. The token account is the defender's associated one, so an attacker can't
+// pick an empty account; armor the defender no longer holds there doesn't protect them.
+fn armor_rating(accounts: &Attack) -> Result<u8> {
+    let armor = match accounts.defender.equipment.armor {
+        Some(armor) => armor,
+        None => return Ok(0),
+    };
+    let (metadata, token_account) = match (&accounts.armor_metadata, &accounts.armor_token_account)
+    {
+        (Some(metadata), Some(token_account)) => (metadata, token_account),
+        _ => return err!(CustomError::ArmorAccountsMissing),
+    };
+    require_keys_eq!(metadata.mint, armor, CustomError::ArmorMismatch);
+    require_keys_eq!(
+        token_account.key(),
+        get_associated_token_address(&accounts.defender.owner, &armor),
+        CustomError::ArmorAccountMismatch
+    );
+    if token_account.owner != accounts.defender.owner || token_account.amount < 1 {
+        return Ok(0);
+    }
+    Ok(modified_stat(0, metadata.stat_bonus))
+}
+
+fn loot_account<'info, T: ToAccountInfo<'info>>(account: &Option<T>) -> Result<AccountInfo<'info>> {
+    account
+        .as_ref()
+        .map(|account| account.to_account_info())
+        .ok_or_else(|| error!(CustomError::LootAccountsMissing))
+}
+
+// Mints LOOT_AMOUNT of the item the loot table picks to the attacker's owner, who pays for
+// their token account if it's new. Signing as the combat authority is what lets this mint items
+// asset_module marks loot_only.
+fn drop_loot(accounts: &Attack, seed: &[u8], signer_seeds: &[&[u8]]) -> Result<()> {
+    let signer = &[signer_seeds];
+    let loot_table = accounts
+        .loot_table
+        .as_ref()
+        .ok_or(CustomError::LootAccountsMissing)?;
+    let picked = pick_loot(seed, &loot_table.entries).ok_or(CustomError::InvalidLootTable)?;
+    let loot_mint = loot_account(&accounts.loot_mint)?;
+    require_keys_eq!(loot_mint.key(), picked, CustomError::WrongLootMint);
+    let loot_ctx = CpiContext::new_with_signer(
+        loot_account(&accounts.asset_program)?,
+        asset_module::cpi::accounts::MintItem {
+            authority: accounts.combat_authority.to_account_info(),
+            payer: accounts.owner.to_account_info(),
+            mint: loot_mint,
+            mint_config: loot_account(&accounts.loot_mint_config)?,
+            mint_authority: loot_account(&accounts.loot_mint_authority)?,
+            item_metadata: loot_account(&accounts.loot_item_metadata)?,
+            item_type: loot_account(&accounts.loot_item_type)?,
+            recipient: accounts.owner.to_account_info(),
+            to: loot_account(&accounts.loot_token_account)?,
+            token_program: loot_account(&accounts.token_program)?,
+            associated_token_program: loot_account(&accounts.associated_token_program)?,
+            system_program: loot_account(&accounts.system_program)?,
+            rent: loot_account(&accounts.rent)?,
+        },
+        signer,
+    );
+    asset_module::cpi::mint_item(loot_ctx, LOOT_AMOUNT)
+}
+
+// Moves the defender's staked item out of its escrow to the attacker's token account. The empty
+// escrow stays open for the next stake. An escrow frozen by the item's freeze authority is
+// skipped, so it can't make the defender unkillable.
+fn transfer_staked_loot<'info>(
+    accounts: &Attack<'info>,
+    staked: Pubkey,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let (from, to) = match (&accounts.staked_loot_account, &accounts.loot_recipient) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return err!(CustomError::LootAccountsMissing),
+    };
+    require_keys_eq!(from.key(), staked, CustomError::StakedLootMismatch);
+    if from.amount < LOOT_AMOUNT || from.is_frozen() {
+        return Ok(());
+    }
+    let signer = &[signer_seeds];
+    let transfer_ctx = CpiContext::new_with_signer(
+        loot_account(&accounts.token_program)?,
+        Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: accounts.combat_authority.to_account_info(),
+        },
+        signer,
+    );
+    token::transfer(transfer_ctx, LOOT_AMOUNT)?;
+    emit!(LootSeized {
+        attacker: accounts.attacker.key(),
+        defender: accounts.defender.key(),
+        mint: from.mint,
+    });
+    Ok(())
+}
+
+// Burns the worn-out copy through asset_module. The owner signed use_item, so their signature
+// authorizes the burn.
+fn burn_worn_item(accounts: &UseItem) -> Result<()> {
+    let burn_accounts = (
+        &accounts.mint,
+        &accounts.item_token_account,
+        &accounts.asset_program,
+        &accounts.token_program,
+    );
+    let (mint, item_token_account, asset_program, token_program) = match burn_accounts {
+        (Some(mint), Some(from), Some(asset_program), Some(token_program)) => {
+            (mint, from, asset_program, token_program)
+        }
+        _ => return err!(CustomError::BurnAccountsMissing),
+    };
+    let burn_ctx = CpiContext::new(
+        asset_program.to_account_info(),
+        asset_module::cpi::accounts::BurnItem {
+            authority: accounts.owner.to_account_info(),
+            mint: mint.to_account_info(),
+            from: item_token_account.to_account_info(),
+            token_program: token_program.to_account_info(),
+            mint_config: accounts.mint_config.as_ref().map(|config| config.to_account_info()),
+            item_metadata: accounts
+                .item_metadata
+                .as_ref()
+                .map(|metadata| metadata.to_account_info()),
+            item_type: accounts.item_type.as_ref().map(|item_type| item_type.to_account_info()),
+        },
+    );
+    asset_module::cpi::burn_item(burn_ctx, 1)
+}
+
+// Respawning is allowed from this timestamp on.
+fn respawn_ready_at(defeated_at: i64) -> i64 {
+    defeated_at.saturating_add(RESPAWN_COOLDOWN_SECS)
+}
+
+fn check_attack_cooldown(last_attack_ts: i64, now: i64, cooldown_secs: i64) -> Result<()> {
+    require!(
+        now.saturating_sub(last_attack_ts) >= cooldown_secs,
+        CustomError::AttackOnCooldown
+    );
+    Ok(())
+}
+
+// Every `player` is a state_module account: Account<PlayerState> rejects anything not owned by
+// game_common::ID, which is state_module's program id. This program only writes CombatState.
+#[derive(Accounts)]
+pub struct EnterCombat<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, seeds::program = state_module::ID)]
+    pub config: Account<'info, GameConfig>,
+    #[account(
+        init,
+        payer = owner,
+        space = CombatState::SPACE,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// The player needn't be on the current PlayerState version to migrate its combat account.
+#[derive(Accounts)]
+pub struct MigrateCombat<'info> {
+    #[account(has_one = owner)]
+    pub player: Account<'info, PlayerState>,
+    /// CHECK: older layouts don't deserialize as CombatState, so migrate_combat checks the
+    /// discriminator by hand.
+    #[account(mut, owner = crate::ID, seeds = [COMBAT_SEED, player.key().as_ref()], bump)]
+    pub combat: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct Attack<'info> {
-    #[account(mut, has_one = owner)]
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = attacker.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !attacker.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub attacker: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = attacker_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, attacker.key().as_ref()],
+        bump = attacker_combat.bump
+    )]
+    pub attacker_combat: Account<'info, CombatState>,
+    #[account(constraint = defender.version == PLAYER_VERSION @ PlayerError::MigrationRequired)]
+    pub defender: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = defender_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, defender.key().as_ref()],
+        bump = defender_combat.bump
+    )]
+    pub defender_combat: Account<'info, CombatState>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, seeds::program = state_program.key())]
+    pub config: Account<'info, GameConfig>,
+    /// CHECK: state_module's Leaderboard; validated by award_combat_experience.
+    #[account(mut)]
+    pub leaderboard: UncheckedAccount<'info>,
+    /// CHECK: signer PDA for state_module and asset_module CPIs; holds no data. It is the only
+    /// minter asset_module accepts for loot_only items.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump)]
+    pub combat_authority: UncheckedAccount<'info>,
+    /// CHECK: address-checked; read raw since the sysvar is too large to deserialize.
+    #[account(address = sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+    pub state_program: Program<'info, StateModule>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    // Loot accounts are only needed when attacking with `drop_on_kill`. The loot table picks
+    // the mint; asset_module's mint_item validates the rest. token_program is also used to
+    // seize staked loot.
+    #[account(seeds = [LOOT_TABLE_SEED], bump = loot_table.bump)]
+    pub loot_table: Option<Account<'info, LootTable>>,
+    /// CHECK: must be the loot table's pick; validated by mint_item.
+    #[account(mut)]
+    pub loot_mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by mint_item.
+    #[account(mut)]
+    pub loot_mint_config: Option<UncheckedAccount<'info>>,
+    /// CHECK: asset_module's mint authority PDA; validated by mint_item.
+    pub loot_mint_authority: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by mint_item.
+    pub loot_item_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by mint_item.
+    #[account(mut)]
+    pub loot_item_type: Option<UncheckedAccount<'info>>,
+    /// CHECK: the owner's associated token account for loot_mint; validated by mint_item.
+    #[account(mut)]
+    pub loot_token_account: Option<UncheckedAccount<'info>>,
+    pub asset_program: Option<Program<'info, AssetModule>>,
+    pub token_program: Option<Program<'info, Token>>,
+    /// CHECK: validated by mint_item.
+    pub associated_token_program: Option<UncheckedAccount<'info>>,
+    pub system_program: Option<Program<'info, System>>,
+    pub rent: Option<Sysvar<'info, Rent>>,
+    // Account<ItemMetadata> only accepts accounts owned by asset_module.
+    pub weapon_metadata: Option<Account<'info, ItemMetadata>>,
+    pub weapon_token_account: Option<Account<'info, TokenAccount>>,
+    // Both required when the defender has armor equipped.
+    pub armor_metadata: Option<Account<'info, ItemMetadata>>,
+    pub armor_token_account: Option<Account<'info, TokenAccount>>,
+    // Required when the killing blow lands on a defender with staked loot; loot_recipient
+    // receives the item and must be for the same mint.
+    #[account(mut)]
+    pub staked_loot_account: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub loot_recipient: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct StakeLoot<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    #[account(
+        mut,
+        token::mint = loot_mint,
+        token::authority = owner
+    )]
+    pub loot_token_account: Account<'info, TokenAccount>,
+    pub loot_mint: Account<'info, Mint>,
+    // Left open and empty when a kill pays it out, so staking the same item again reuses it.
+    #[account(
+        init_if_needed,
+        payer = owner,
+        token::mint = loot_mint,
+        token::authority = combat_authority,
+        seeds = [STAKE_ESCROW_SEED, combat.key().as_ref(), loot_mint.key().as_ref()],
+        bump
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+    /// CHECK: PDA that holds the escrow; holds no data.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump)]
+    pub combat_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeLoot<'info> {
+    #[account(has_one = owner)]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.staked_loot == Some(escrow.key()) @ CustomError::NoLootStaked,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    #[account(mut)]
+    pub escrow: Account<'info, TokenAccount>,
+    // Receives the item back; any token account for the escrow's mint will do.
+    #[account(
+        mut,
+        constraint = loot_token_account.mint == escrow.mint @ CustomError::WrongItemMint
+    )]
+    pub loot_token_account: Account<'info, TokenAccount>,
+    /// CHECK: PDA that holds the escrow; holds no data.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump)]
+    pub combat_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Defend<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Heal<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LearnSkill<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    pub owner: Signer<'info>,
+}
+
+// Targets are passed as writable CombatState accounts in remaining_accounts.
+#[derive(Accounts)]
+pub struct AreaAttack<'info> {
+    #[account(
+        has_one = owner,
+        constraint = attacker.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !attacker.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub attacker: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = attacker_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, attacker.key().as_ref()],
+        bump = attacker_combat.bump
+    )]
+    pub attacker_combat: Account<'info, CombatState>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump, seeds::program = state_module::ID)]
+    pub config: Account<'info, GameConfig>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CastSkill<'info> {
+    #[account(
+        has_one = owner,
+        constraint = caster.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !caster.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub caster: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = caster_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, caster.key().as_ref()],
+        bump = caster_combat.bump
+    )]
+    pub caster_combat: Account<'info, CombatState>,
+    #[account(constraint = target.version == PLAYER_VERSION @ PlayerError::MigrationRequired)]
+    pub target: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = target_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, target.key().as_ref()],
+        bump = target_combat.bump
+    )]
+    pub target_combat: Account<'info, CombatState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Respawn<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyEffect<'info> {
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDots<'info> {
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, player.key().as_ref()],
+        bump = combat.bump
+    )]
+    pub combat: Account<'info, CombatState>,
+}
+
+#[derive(Accounts)]
+pub struct SetLootTable<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = LootTable::SPACE,
+        seeds = [LOOT_TABLE_SEED],
+        bump
+    )]
+    pub loot_table: Account<'info, LootTable>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitMatchQueue<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = MatchQueue::SPACE,
+        seeds = [MATCH_QUEUE_SEED],
+        bump
+    )]
+    pub queue: Account<'info, MatchQueue>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Enqueue<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut, seeds = [MATCH_QUEUE_SEED], bump = queue.bump)]
+    pub queue: Account<'info, MatchQueue>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DequeueMatch<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
     pub player: Account<'info, PlayerState>,
+    #[account(mut, seeds = [MATCH_QUEUE_SEED], bump = queue.bump)]
+    pub queue: Account<'info, MatchQueue>,
     pub owner: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct Challenge<'info> {
+    #[account(
+        has_one = owner,
+        constraint = challenger.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !challenger.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub challenger: Account<'info, PlayerState>,
+    #[account(
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, challenger.key().as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(constraint = opponent.owner != owner.key() @ CustomError::SameOwnerAttack)]
+    pub opponent: Account<'info, PlayerState>,
+    #[account(
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, opponent.key().as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    #[account(
+        init,
+        payer = owner,
+        space = Battle::SPACE,
+        seeds = [BATTLE_SEED, challenger.key().as_ref(), opponent.key().as_ref()],
+        bump
+    )]
+    pub battle: Account<'info, Battle>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptChallenge<'info> {
+    #[account(
+        mut,
+        seeds = [BATTLE_SEED, battle.challenger.as_ref(), battle.opponent.as_ref()],
+        bump = battle.bump
+    )]
+    pub battle: Account<'info, Battle>,
+    // Both players are locked in state_module for the length of the battle.
+    #[account(mut, address = battle.challenger)]
+    pub challenger: Account<'info, PlayerState>,
+    #[account(mut, address = battle.opponent)]
+    pub opponent: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, battle.challenger.as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(
+        mut,
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, battle.opponent.as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    /// CHECK: signer PDA for state_module's set_player_locked; holds no data.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump)]
+    pub combat_authority: UncheckedAccount<'info>,
+    pub state_program: Program<'info, StateModule>,
+    pub owner: Signer<'info>,
+}
+
+// Shared by take_turn, forfeit and claim_timeout_victory; each checks which side signed.
+#[derive(Accounts)]
+pub struct BattleTurn<'info> {
+    #[account(
+        mut,
+        seeds = [BATTLE_SEED, battle.challenger.as_ref(), battle.opponent.as_ref()],
+        bump = battle.bump
+    )]
+    pub battle: Account<'info, Battle>,
+    // Mutable so finish_battle can unlock both players in state_module.
+    #[account(mut, address = battle.challenger)]
+    pub challenger: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, battle.challenger.as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(mut, address = battle.opponent)]
+    pub opponent: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, battle.opponent.as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    /// CHECK: signer PDA for state_module's set_player_locked; holds no data.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump)]
+    pub combat_authority: UncheckedAccount<'info>,
+    pub state_program: Program<'info, StateModule>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBattle<'info> {
+    #[account(
+        mut,
+        close = challenger_owner,
+        constraint = battle.status == BattleStatus::Finished @ CustomError::BattleNotFinished,
+        seeds = [BATTLE_SEED, battle.challenger.as_ref(), battle.opponent.as_ref()],
+        bump = battle.bump
+    )]
+    pub battle: Account<'info, Battle>,
+    /// CHECK: receives the battle account's rent; address-checked.
+    #[account(mut, address = battle.challenger_owner)]
+    pub challenger_owner: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitDurability<'info> {
+    pub player: Account<'info, PlayerState>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        space = Durability::SPACE,
+        seeds = [DURABILITY_SEED, player.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub durability: Account<'info, Durability>,
+    #[account(
+        has_one = admin,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        seeds::program = state_module::ID
+    )]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UseItem<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        seeds = [DURABILITY_SEED, player.key().as_ref(), durability.mint.as_ref()],
+        bump = durability.bump
+    )]
+    pub durability: Account<'info, Durability>,
+    pub owner: Signer<'info>,
+    // Burn accounts are only needed for the use that breaks a burn_on_break item; burn_item
+    // checks the token account against the mint.
+    #[account(mut, address = durability.mint @ CustomError::WrongItemMint)]
+    pub mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub item_token_account: Option<Account<'info, TokenAccount>>,
+    pub asset_program: Option<Program<'info, AssetModule>>,
+    pub token_program: Option<Program<'info, Token>>,
+    /// CHECK: the item's asset_module MintConfig, if it has one; validated by burn_item.
+    #[account(mut)]
+    pub mint_config: Option<UncheckedAccount<'info>>,
+    /// CHECK: the item's asset_module ItemMetadata, passed along with mint_config.
+    pub item_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: the item's asset_module ItemType, passed along with mint_config.
+    #[account(mut)]
+    pub item_type: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMatch<'info> {
+    #[account(
+        has_one = owner,
+        constraint = challenger.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !challenger.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub challenger: Account<'info, PlayerState>,
+    #[account(constraint = opponent.owner != owner.key() @ CustomError::SameOwnerAttack)]
+    pub opponent: Account<'info, PlayerState>,
+    #[account(
+        init,
+        payer = owner,
+        space = Match::SPACE,
+        seeds = [MATCH_SEED, challenger.key().as_ref(), opponent.key().as_ref()],
+        bump
+    )]
+    pub match_account: Account<'info, Match>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// Both players must be standing when the match starts, so a stale defeat can't decide it.
+#[derive(Accounts)]
+pub struct AcceptMatch<'info> {
+    #[account(
+        has_one = owner,
+        constraint = opponent.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !opponent.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub opponent: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = match_account.opponent == opponent.key() @ CustomError::NotMatchOpponent,
+        constraint = !match_account.accepted @ CustomError::MatchAlreadyAccepted,
+        seeds = [MATCH_SEED, match_account.challenger.as_ref(), opponent.key().as_ref()],
+        bump = match_account.bump
+    )]
+    pub match_account: Account<'info, Match>,
+    #[account(
+        constraint = !challenger_combat.defeated @ CustomError::AlreadyDefeated,
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, match_account.challenger.as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(
+        constraint = !opponent_combat.defeated @ CustomError::AlreadyDefeated,
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, opponent.key().as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMatch<'info> {
+    #[account(
+        mut,
+        close = challenger_owner,
+        constraint = match_account.accepted @ CustomError::MatchNotAccepted,
+        seeds = [MATCH_SEED, match_account.challenger.as_ref(), match_account.opponent.as_ref()],
+        bump = match_account.bump
+    )]
+    pub match_account: Account<'info, Match>,
+    #[account(
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, match_account.challenger.as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, match_account.opponent.as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    /// CHECK: receives the pot; checked against the winning side's wallet.
+    #[account(mut)]
+    pub winner: UncheckedAccount<'info>,
+    /// CHECK: receives the match account's rent; address-checked.
+    #[account(mut, address = match_account.challenger_owner)]
+    pub challenger_owner: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMatch<'info> {
+    #[account(
+        mut,
+        close = owner,
+        constraint = match_account.challenger_owner == owner.key()
+            @ CustomError::NotMatchChallenger,
+        constraint = !match_account.accepted @ CustomError::MatchAlreadyAccepted,
+        seeds = [MATCH_SEED, match_account.challenger.as_ref(), match_account.opponent.as_ref()],
+        bump = match_account.bump
+    )]
+    pub match_account: Account<'info, Match>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundMatch<'info> {
+    #[account(
+        mut,
+        close = challenger_owner,
+        constraint = match_account.accepted @ CustomError::MatchNotAccepted,
+        seeds = [MATCH_SEED, match_account.challenger.as_ref(), match_account.opponent.as_ref()],
+        bump = match_account.bump
+    )]
+    pub match_account: Account<'info, Match>,
+    #[account(
+        constraint = challenger_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, match_account.challenger.as_ref()],
+        bump = challenger_combat.bump
+    )]
+    pub challenger_combat: Account<'info, CombatState>,
+    #[account(
+        constraint = opponent_combat.version == COMBAT_VERSION @ CustomError::MigrationRequired,
+        seeds = [COMBAT_SEED, match_account.opponent.as_ref()],
+        bump = opponent_combat.bump
+    )]
+    pub opponent_combat: Account<'info, CombatState>,
+    /// CHECK: receives the challenger's wager and the rent; address-checked.
+    #[account(mut, address = match_account.challenger_owner)]
+    pub challenger_owner: UncheckedAccount<'info>,
+    /// CHECK: receives the opponent's wager; address-checked.
+    #[account(mut, address = match_account.opponent_owner)]
+    pub opponent_owner: UncheckedAccount<'info>,
+}
+
+#[account]
+#[derive(Default)]
+pub struct Match {
+    pub challenger: Pubkey,
+    pub challenger_owner: Pubkey,
+    pub opponent: Pubkey,
+    // Set when the opponent accepts.
+    pub opponent_owner: Pubkey,
+    // Staked by each side, so the pot is twice this.
+    pub wager: u64,
+    pub accepted: bool,
+    pub created_at: i64,
+    pub bump: u8,
+    pub accepted_at: i64,
+}
+
+impl Match {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 1 + 8 + 1 + 8;
+
+    // Returns the winning player and wallet once one side has been defeated by the other since
+    // acceptance. A defeat by anyone else or by poison doesn't count. defeated_at survives a
+    // respawn, so getting back up doesn't undo a loss; if both sides have fallen, the first to
+    // fall lost. None while undecided or on a same-second tie.
+    pub fn winner(
+        &self,
+        challenger: &CombatState,
+        opponent: &CombatState,
+    ) -> Option<(Pubkey, Pubkey)> {
+        let fell_at = |combat: &CombatState, foe: Pubkey| {
+            Some(combat.defeated_at)
+                .filter(|&at| at >= self.accepted_at && combat.defeated_by == Some(foe))
+        };
+        let challenger_won = match (
+            fell_at(challenger, self.opponent),
+            fell_at(opponent, self.challenger),
+        ) {
+            (None, Some(_)) => true,
+            (Some(_), None) => false,
+            (Some(challenger_at), Some(opponent_at)) if challenger_at != opponent_at => {
+                opponent_at < challenger_at
+            }
+            _ => return None,
+        };
+        Some(if challenger_won {
+            (self.challenger, self.challenger_owner)
+        } else {
+            (self.opponent, self.opponent_owner)
+        })
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct QueueEntry {
+    pub player: Pubkey,
+    // Level when the player queued.
+    pub level: u8,
+}
+
+#[account]
+pub struct LootTable {
+    pub entries: Vec<LootEntry>,
+    pub bump: u8,
+}
+
+impl LootTable {
+    pub const SPACE: usize = 8 + 4 + LootEntry::SIZE * MAX_LOOT_ENTRIES + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LootEntry {
+    // A loot_only item whose mint config names the combat authority.
+    pub mint: Pubkey,
+    // Relative to the other entries' weights.
+    pub weight: u16,
+}
+
+impl LootEntry {
+    pub const SIZE: usize = 32 + 2;
+}
+
 #[account]
-pub struct PlayerState {
-    pub owner: Pubkey,
-    pub hp: u8,
+pub struct MatchQueue {
+    // Only the first `len` entries are in use, oldest first.
+    pub entries: [QueueEntry; MAX_QUEUE_LEN],
+    pub len: u8,
+    pub bump: u8,
+}
+
+impl MatchQueue {
+    pub const SPACE: usize = 8 + (32 + 1) * MAX_QUEUE_LEN + 1 + 1;
+
+    fn position(&self, player: &Pubkey) -> Option<usize> {
+        self.entries[..self.len as usize]
+            .iter()
+            .position(|entry| entry.player == *player)
+    }
+
+    // The other waiting player nearest `level`, earliest queued on a tie.
+    fn closest(&self, player: &Pubkey, level: u8) -> Option<Pubkey> {
+        self.entries[..self.len as usize]
+            .iter()
+            .filter(|entry| entry.player != *player)
+            .min_by_key(|entry| entry.level.abs_diff(level))
+            .map(|entry| entry.player)
+    }
+
+    // Shifts later entries down so the queue stays in arrival order.
+    fn remove(&mut self, player: &Pubkey) {
+        if let Some(index) = self.position(player) {
+            let len = self.len as usize;
+            self.entries.copy_within(index + 1..len, index);
+            self.entries[len - 1] = QueueEntry::default();
+            self.len -= 1;
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BattleStatus {
+    Challenged,
+    Active,
+    Finished,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BattleAction {
+    Attack,
+    Defend,
+}
+
+#[account]
+pub struct Battle {
+    pub challenger: Pubkey,
+    // Receives the rent when the finished battle is closed.
+    pub challenger_owner: Pubkey,
+    pub opponent: Pubkey,
+    pub status: BattleStatus,
+    // Player account due to act next.
+    pub turn: Pubkey,
+    pub turn_deadline: i64,
+    // None for a battle abandoned before it was accepted.
+    pub winner: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl Battle {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 1 + 32 + 8 + 33 + 1;
+}
+
+#[account]
+pub struct Durability {
+    pub player: Pubkey,
+    pub mint: Pubkey,
+    pub max: u16,
+    pub current: u16,
+    pub burn_on_break: bool,
+    pub bump: u8,
+}
+
+impl Durability {
+    pub const SPACE: usize = 8 + 32 + 32 + 2 + 2 + 1 + 1;
+}
+
+#[account]
+#[derive(Default)]
+pub struct CombatState {
+    // state_module player account this state belongs to.
+    pub player: Pubkey,
+    pub hp: u16,
+    // Copied from the player's max_hp when entering combat.
+    pub max_hp: u16,
+    pub last_active: i64,
+    pub death_count: u32,
+    // Set when damage takes hp to zero; cleared by respawn.
+    pub defeated: bool,
+    pub last_attack_ts: i64,
+    pub defeated_at: i64,
+    // Mixed into every crit roll so two attacks in the same slot roll differently.
+    pub attack_nonce: u64,
+    pub bump: u8,
+    pub effects: [ActiveEffect; MAX_EFFECTS],
+    pub stamina: u8,
+    pub max_stamina: u8,
+    pub last_stamina_update: i64,
+    // Set by defend; consumed by the next hit taken or cleared by attacking.
+    pub guarding: bool,
+    // The escrow token account stake_loot moved the item into; paid out on defeat.
+    pub staked_loot: Option<Pubkey>,
+    // Zero, as read from an account that predates it, means migrate_combat hasn't run.
+    pub version: u8,
+    // Set while locked into a Battle; blocks attack and heal outside of it.
+    pub in_battle: bool,
+    pub mana: u16,
+    pub max_mana: u16,
+    pub last_mana_update: i64,
+    // Bit n set means skill id n has been learned.
+    pub skills: u32,
+    pub poison_stacks: u8,
+    pub poison_until_ts: i64,
+    // Poison has been applied up to here; see tick_poison.
+    pub last_poison_tick: i64,
+    // The player who landed the last killing blow, or None after a poison death. Like
+    // defeated_at, it survives a respawn.
+    pub defeated_by: Option<Pubkey>,
+}
+
+impl CombatState {
+    pub const SPACE: usize = 8 // discriminator
+        + 32 // player
+        + 2 // hp
+        + 2 // max_hp
+        + 8 // last_active
+        + 4 // death_count
+        + 1 // defeated
+        + 8 // last_attack_ts
+        + 8 // defeated_at
+        + 8 // attack_nonce
+        + 1 // bump
+        + ActiveEffect::SIZE * MAX_EFFECTS // effects
+        + 2 // stamina, max_stamina
+        + 8 // last_stamina_update
+        + 1 // guarding
+        + 33 // staked_loot
+        + 1 // version
+        + 1 // in_battle
+        + 2 // mana
+        + 2 // max_mana
+        + 8 // last_mana_update
+        + 4 // skills
+        + 1 // poison_stacks
+        + 8 // poison_until_ts
+        + 8 // last_poison_tick
+        + 33; // defeated_by
+
+    fn regen_stamina(&mut self, now: i64) {
+        let stamina = regenerate(
+            self.stamina as u64,
+            self.max_stamina as u64,
+            &mut self.last_stamina_update,
+            now,
+            STAMINA_REGEN_SECS,
+        );
+        self.stamina = stamina as u8;
+    }
+
+    fn regen_mana(&mut self, now: i64) {
+        let mana = regenerate(
+            self.mana as u64,
+            self.max_mana as u64,
+            &mut self.last_mana_update,
+            now,
+            MANA_REGEN_SECS,
+        );
+        self.mana = mana as u16;
+    }
+
+    // Back at full hp and cured of poison, with the death counted.
+    fn revive(&mut self, now: i64) {
+        self.hp = self.max_hp;
+        self.defeated = false;
+        self.poison_stacks = 0;
+        self.death_count = self.death_count.saturating_add(1);
+        self.last_active = now;
+    }
+
+    // Over-healing clamps to max_hp. Returns the hp actually restored.
+    fn restore_hp(&mut self, amount: u16) -> u16 {
+        let healed = amount.min(self.max_hp.saturating_sub(self.hp));
+        self.hp += healed;
+        healed
+    }
+
+    // Abilities call this before applying their effect, so a failed cast changes nothing.
+    fn spend_mana(&mut self, cost: u16, now: i64) -> Result<()> {
+        self.regen_mana(now);
+        self.mana = self
+            .mana
+            .checked_sub(cost)
+            .ok_or(CustomError::InsufficientMana)?;
+        Ok(())
+    }
+
+    // Applies the poison damage for every full tick since last_poison_tick, up to
+    // poison_until_ts, and clears the poison once it has expired. Returns whether the poison
+    // was the killing blow.
+    fn tick_poison(&mut self, now: i64) -> Result<bool> {
+        if self.poison_stacks == 0 || self.defeated {
+            return Ok(false);
+        }
+        let end = now.min(self.poison_until_ts);
+        let ticks = end.saturating_sub(self.last_poison_tick).max(0) / POISON_TICK_SECS;
+        self.last_poison_tick += ticks * POISON_TICK_SECS;
+        let damage = (ticks as u64)
+            .saturating_mul(self.poison_stacks as u64 * POISON_DAMAGE_PER_STACK)
+            .min(u16::MAX as u64) as u16;
+        if now >= self.poison_until_ts {
+            self.poison_stacks = 0;
+        }
+        if damage == 0 {
+            return Ok(false);
+        }
+        apply_damage(self, damage, now, None)
+    }
+
+    // Expired effects are cleared lazily, whenever the account is next written.
+    fn clear_expired_effects(&mut self, slot: u64) {
+        for effect in self.effects.iter_mut() {
+            if !effect.is_active(slot) {
+                *effect = ActiveEffect::default();
+            }
+        }
+    }
+
+    fn effect_total(&self, kind: u8, slot: u64) -> i16 {
+        self.effects
+            .iter()
+            .filter(|effect| effect.kind == kind && effect.is_active(slot))
+            .fold(0, |total, effect| total.saturating_add(effect.magnitude))
+    }
+}
+
+// CombatState before `version` was added, read only by migrate_combat.
+#[derive(AnchorDeserialize)]
+struct LegacyCombatState {
+    player: Pubkey,
+    hp: u8,
+    max_hp: u8,
+    last_active: i64,
+    death_count: u32,
+    defeated: bool,
+    last_attack_ts: i64,
+    defeated_at: i64,
+    attack_nonce: u64,
+    bump: u8,
+    effects: [ActiveEffect; MAX_EFFECTS],
+    stamina: u8,
+    max_stamina: u8,
+    last_stamina_update: i64,
+    guarding: bool,
+    staked_loot: Option<Pubkey>,
+}
+
+impl LegacyCombatState {
+    const SPACE: usize = CombatState::SPACE
+        - 2 // hp and max_hp were u8
+        - 1 // version
+        - 1 // in_battle
+        - 2 // mana
+        - 2 // max_mana
+        - 8 // last_mana_update
+        - 4 // skills
+        - 1 // poison_stacks
+        - 8 // poison_until_ts
+        - 8; // last_poison_tick
+
+    // Produces the version 1 layout; migrate_combat takes it the rest of the way.
+    fn upgrade(self) -> CombatState {
+        CombatState {
+            player: self.player,
+            hp: u16::from(self.hp),
+            max_hp: u16::from(self.max_hp),
+            last_active: self.last_active,
+            death_count: self.death_count,
+            defeated: self.defeated,
+            last_attack_ts: self.last_attack_ts,
+            defeated_at: self.defeated_at,
+            attack_nonce: self.attack_nonce,
+            bump: self.bump,
+            effects: self.effects,
+            stamina: self.stamina,
+            max_stamina: self.max_stamina,
+            last_stamina_update: self.last_stamina_update,
+            guarding: self.guarding,
+            staked_loot: self.staked_loot,
+            version: 1,
+            in_battle: false,
+            mana: 0,
+            max_mana: 0,
+            last_mana_update: 0,
+            skills: 0,
+            poison_stacks: 0,
+            poison_until_ts: 0,
+            last_poison_tick: 0,
+            defeated_by: None,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ActiveEffect {
+    pub kind: u8,
+    pub magnitude: i16,
+    pub expires_at_slot: u64,
+}
+
+impl ActiveEffect {
+    pub const SIZE: usize = 1 + 2 + 8;
+
+    // Active up to, but not including, expires_at_slot. Empty slots have expires_at_slot 0.
+    pub fn is_active(&self, slot: u64) -> bool {
+        slot < self.expires_at_slot
+    }
+}
+
+#[event]
+pub struct PlayerDefeated {
+    pub player: Pubkey,
+}
+
+// Emitted for every hit, with `damage_dealt` after defense and crits. `fatal` marks the
+// killing blow.
+#[event]
+pub struct CombatEvent {
+    pub attacker: Pubkey,
+    pub defender: Pubkey,
+    pub damage_dealt: u16,
+    pub defender_hp_after: u16,
+    pub fatal: bool,
+}
+
+// `amount` is what was actually restored, which clamping can make less than requested.
+#[event]
+pub struct PlayerHealed {
+    pub player: Pubkey,
+    pub amount: u16,
+    pub hp: u16,
+}
+
+#[event]
+pub struct PlayerRespawned {
+    pub player: Pubkey,
+    pub hp: u16,
+    pub death_count: u32,
+}
+
+#[event]
+pub struct DodgeEvent {
+    pub attacker: Pubkey,
+    pub defender: Pubkey,
+}
+
+#[event]
+pub struct MatchResolved {
+    pub match_account: Pubkey,
+    pub winner: Pubkey,
+    pub pot: u64,
+}
+
+#[event]
+pub struct KillRewarded {
+    pub attacker: Pubkey,
+    pub defender: Pubkey,
+    pub xp: u64,
+}
+
+#[event]
+pub struct LootSeized {
+    pub attacker: Pubkey,
+    pub defender: Pubkey,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct BattleFinished {
+    pub battle: Pubkey,
+    pub winner: Option<Pubkey>,
+}
+
+#[event]
+pub struct ItemWornOut {
+    pub player: Pubkey,
+    pub mint: Pubkey,
+    pub burned: bool,
+}
+
+#[event]
+pub struct MatchFound {
+    pub player: Pubkey,
+    pub opponent: Pubkey,
 }
 
 #[error_code]
 pub enum CustomError {
-    #[msg("Player would be defeated.")]
-    PlayerDefeated,
+    #[msg("Player is already defeated.")]
+    AlreadyDefeated,
+    #[msg("Player has not been defeated.")]
+    NotDefeated,
+    #[msg("Players cannot attack themselves.")]
+    CannotAttackSelf,
+    #[msg("Defeated players cannot attack.")]
+    AttackerDefeated,
+    #[msg("Attack is on cooldown.")]
+    AttackOnCooldown,
+    #[msg("Arithmetic overflow.")]
+    ArithmeticOverflow,
+    #[msg("SlotHashes sysvar has no entries.")]
+    SlotHashesUnavailable,
+    #[msg("Heal amount must be greater than zero.")]
+    InvalidHealAmount,
+    #[msg("Defeated players must respawn instead of healing.")]
+    CannotHealDefeated,
+    #[msg("Respawn is on cooldown until defeated_at + RESPAWN_COOLDOWN_SECS.")]
+    RespawnCooldownActive,
+    #[msg("drop_on_kill requires the loot table and loot accounts.")]
+    LootAccountsMissing,
+    #[msg("Unknown effect kind or zero duration.")]
+    InvalidEffect,
+    #[msg("All effect slots are in use.")]
+    EffectSlotsFull,
+    #[msg("Not enough stamina to attack.")]
+    NotEnoughStamina,
+    #[msg("Wager must be greater than zero.")]
+    InvalidWager,
+    #[msg("Only the challenged player can accept this match.")]
+    NotMatchOpponent,
+    #[msg("Only the challenger can cancel this match.")]
+    NotMatchChallenger,
+    #[msg("Match has already been accepted.")]
+    MatchAlreadyAccepted,
+    #[msg("Match has not been accepted.")]
+    MatchNotAccepted,
+    #[msg("Neither side has been defeated since the match was accepted.")]
+    MatchNotConcluded,
+    #[msg("Winner account does not belong to the winning player.")]
+    WrongMatchWinner,
+    #[msg("Match can only be cancelled after the accept timeout.")]
+    MatchNotExpired,
+    #[msg("Match can only be refunded after the resolve deadline.")]
+    MatchDeadlineNotReached,
+    #[msg("Match has a winner; resolve it instead.")]
+    MatchConcluded,
+    #[msg("Weapon metadata and token account must be passed together.")]
+    WeaponAccountsIncomplete,
+    #[msg("Weapon token account is for a different mint.")]
+    WeaponMismatch,
+    #[msg("Attacker does not hold the weapon.")]
+    WeaponNotHeld,
+    #[msg("Weapon is not equipped.")]
+    WeaponNotEquipped,
+    #[msg("Defender has armor equipped; pass its metadata and token account.")]
+    ArmorAccountsMissing,
+    #[msg("Armor metadata is for a different mint than the defender's equipped armor.")]
+    ArmorMismatch,
+    #[msg("Player is already guarding.")]
+    AlreadyGuarding,
+    #[msg("Player already has loot staked.")]
+    LootAlreadyStaked,
+    #[msg("Token account is not the player's staked loot.")]
+    NoLootStaked,
+    #[msg("Staked loot account does not match the defender's stake.")]
+    StakedLootMismatch,
+    #[msg("Durability must be greater than zero.")]
+    InvalidDurability,
+    #[msg("Item is broken.")]
+    ItemBroken,
+    #[msg("Breaking a burn_on_break item requires the burn accounts.")]
+    BurnAccountsMissing,
+    #[msg("Mint does not match the tracked item.")]
+    WrongItemMint,
+    #[msg("Combat account uses an older layout; call migrate_combat first.")]
+    MigrationRequired,
+    #[msg("Players cannot attack accounts owned by the same wallet.")]
+    SameOwnerAttack,
+    #[msg("Player is locked into a battle.")]
+    InBattle,
+    #[msg("Signer is not part of this battle.")]
+    NotBattleParticipant,
+    #[msg("Battle is not waiting to be accepted.")]
+    BattleNotPending,
+    #[msg("Battle is not active.")]
+    BattleNotActive,
+    #[msg("Battle has not finished.")]
+    BattleNotFinished,
+    #[msg("It is not your turn.")]
+    NotYourTurn,
+    #[msg("The turn deadline has passed.")]
+    TurnExpired,
+    #[msg("The turn deadline has not passed yet.")]
+    TurnNotExpired,
+    #[msg("Not enough mana.")]
+    InsufficientMana,
+    #[msg("Unknown skill id.")]
+    UnknownSkill,
+    #[msg("Skill has not been learned.")]
+    SkillNotLearned,
+    #[msg("Too many targets for an area attack.")]
+    TooManyTargets,
+    #[msg("Area attack targets must come in player and combat state pairs.")]
+    AoeAccountsMismatch,
+    #[msg("Player is already in the match queue.")]
+    AlreadyQueued,
+    #[msg("The match queue is full.")]
+    QueueFull,
+    #[msg("No opponent is waiting in the match queue.")]
+    QueueEmpty,
+    #[msg("Loot tables need at most MAX_LOOT_ENTRIES entries and a non-zero weight.")]
+    InvalidLootTable,
+    #[msg("Loot mint is not the loot table's pick.")]
+    WrongLootMint,
+    #[msg("Armor token account is not the defender's associated token account for it.")]
+    ArmorAccountMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests build every CombatState from the zeroed default, setting only what they exercise.
+    fn combat_state() -> CombatState {
+        CombatState::default()
+    }
+
+    impl CombatState {
+        fn with_hp(mut self, hp: u16, max_hp: u16) -> Self {
+            self.hp = hp;
+            self.max_hp = max_hp;
+            self
+        }
+
+        fn with_stamina(mut self, stamina: u8, last_update: i64) -> Self {
+            self.stamina = stamina;
+            self.max_stamina = MAX_STAMINA;
+            self.last_stamina_update = last_update;
+            self
+        }
+
+        fn with_mana(mut self, mana: u16, last_update: i64) -> Self {
+            self.mana = mana;
+            self.max_mana = MAX_MANA;
+            self.last_mana_update = last_update;
+            self
+        }
+
+        fn with_poison(mut self, stacks: u8, since: i64, until: i64) -> Self {
+            self.poison_stacks = stacks;
+            self.last_poison_tick = since;
+            self.poison_until_ts = until;
+            self
+        }
+
+        fn with_effects(mut self, effects: [ActiveEffect; MAX_EFFECTS]) -> Self {
+            self.effects = effects;
+            self
+        }
+
+        fn fallen_at(mut self, at: i64) -> Self {
+            self.defeated = true;
+            self.defeated_at = at;
+            self
+        }
+
+        fn by(mut self, attacker: Pubkey) -> Self {
+            self.defeated_by = Some(attacker);
+            self
+        }
+    }
+
+    fn accepted_match(accepted_at: i64) -> Match {
+        Match {
+            challenger: Pubkey::new_unique(),
+            challenger_owner: Pubkey::new_unique(),
+            opponent: Pubkey::new_unique(),
+            opponent_owner: Pubkey::new_unique(),
+            accepted: true,
+            accepted_at,
+            ..Match::default()
+        }
+    }
+
+    // A queue holding a fresh player at each of `levels`, in order.
+    fn queue_at(levels: &[u8]) -> (MatchQueue, Vec<Pubkey>) {
+        let mut queue = MatchQueue {
+            entries: [QueueEntry::default(); MAX_QUEUE_LEN],
+            len: 0,
+            bump: 0,
+        };
+        let players: Vec<Pubkey> = levels.iter().map(|_| Pubkey::new_unique()).collect();
+        for (&player, &level) in players.iter().zip(levels) {
+            queue.entries[queue.len as usize] = QueueEntry { player, level };
+            queue.len += 1;
+        }
+        (queue, players)
+    }
+
+    #[test]
+    fn queue_pairs_the_closest_level_and_the_earliest_on_a_tie() {
+        let (queue, players) = queue_at(&[1, 9, 5, 7]);
+        let caller = Pubkey::new_unique();
+        assert_eq!(queue.closest(&caller, 6), Some(players[2]));
+        assert_eq!(queue.closest(&caller, 8), Some(players[1]));
+        assert_eq!(queue.closest(&caller, 1), Some(players[0]));
+    }
+
+    #[test]
+    fn queue_never_pairs_a_player_with_themselves() {
+        let (queue, players) = queue_at(&[3]);
+        assert_eq!(queue.closest(&players[0], 3), None);
+        let (empty, _) = queue_at(&[]);
+        assert_eq!(empty.closest(&Pubkey::new_unique(), 3), None);
+    }
+
+    #[test]
+    fn queue_remove_keeps_arrival_order() {
+        let (mut queue, players) = queue_at(&[2, 2, 2]);
+        queue.remove(&players[0]);
+        assert_eq!(queue.len, 2);
+        assert_eq!(queue.position(&players[1]), Some(0));
+        assert_eq!(queue.position(&players[2]), Some(1));
+        assert_eq!(queue.position(&players[0]), None);
+        assert_eq!(queue.closest(&Pubkey::new_unique(), 2), Some(players[1]));
+        // Removing a player who isn't queued leaves the queue alone.
+        queue.remove(&Pubkey::new_unique());
+        assert_eq!(queue.len, 2);
+    }
+
+    #[test]
+    fn match_winner_is_the_side_still_standing() {
+        let m = accepted_match(100);
+        let challenger = (m.challenger, m.challenger_owner);
+        let opponent = (m.opponent, m.opponent_owner);
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(0).by(m.opponent),
+                &combat_state().fallen_at(150).by(m.challenger)
+            ),
+            Some(challenger)
+        );
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(150).by(m.opponent),
+                &combat_state().fallen_at(0).by(m.challenger)
+            ),
+            Some(opponent)
+        );
+    }
+
+    #[test]
+    fn match_winner_ignores_defeats_before_acceptance() {
+        let m = accepted_match(100);
+        let challenger = (m.challenger, m.challenger_owner);
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(0).by(m.opponent),
+                &combat_state().fallen_at(0).by(m.challenger)
+            ),
+            None
+        );
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(99).by(m.opponent),
+                &combat_state().fallen_at(50).by(m.challenger)
+            ),
+            None
+        );
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(99).by(m.opponent),
+                &combat_state().fallen_at(100).by(m.challenger)
+            ),
+            Some(challenger)
+        );
+    }
+
+    #[test]
+    fn match_winner_when_both_fell_is_whoever_fell_last() {
+        let m = accepted_match(100);
+        let challenger = (m.challenger, m.challenger_owner);
+        let opponent = (m.opponent, m.opponent_owner);
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(120).by(m.opponent),
+                &combat_state().fallen_at(110).by(m.challenger)
+            ),
+            Some(challenger)
+        );
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(110).by(m.opponent),
+                &combat_state().fallen_at(120).by(m.challenger)
+            ),
+            Some(opponent)
+        );
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(110).by(m.opponent),
+                &combat_state().fallen_at(110).by(m.challenger)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn match_winner_only_counts_defeats_by_the_other_side() {
+        let m = accepted_match(100);
+        let outsider = Pubkey::new_unique();
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(0).by(m.opponent),
+                &combat_state().fallen_at(150).by(outsider)
+            ),
+            None
+        );
+        let poisoned = combat_state().fallen_at(150);
+        assert_eq!(m.winner(&combat_state().fallen_at(0).by(m.opponent), &poisoned), None);
+        // A third party's kill doesn't decide it, but a later one by the opponent does.
+        let opponent = (m.opponent, m.opponent_owner);
+        assert_eq!(
+            m.winner(
+                &combat_state().fallen_at(120).by(m.opponent),
+                &combat_state().fallen_at(110).by(outsider)
+            ),
+            Some(opponent)
+        );
+    }
+
+    #[test]
+    fn battle_survivor_is_whoever_is_still_standing() {
+        let standing = combat_state();
+        let down = combat_state().fallen_at(0);
+        let (challenger, opponent) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(battle_survivor((challenger, &standing), (opponent, &standing)), None);
+        assert_eq!(
+            battle_survivor((challenger, &standing), (opponent, &down)),
+            Some(Some(challenger))
+        );
+        assert_eq!(
+            battle_survivor((challenger, &down), (opponent, &standing)),
+            Some(Some(opponent))
+        );
+        assert_eq!(battle_survivor((challenger, &down), (opponent, &down)), Some(None));
+    }
+
+    #[test]
+    fn roll_crit_lands_about_crit_chance_percent_of_the_time() {
+        let crits = (0u64..10_000)
+            .filter(|nonce| roll_crit(&nonce.to_le_bytes()))
+            .count();
+        // Expect 1,000; well outside this band would mean a biased roll.
+        assert!((850..1_150).contains(&crits), "crit landed {crits} times");
+    }
+
+    #[test]
+    fn roll_crit_is_deterministic_for_a_seed() {
+        let crit = (0u64..).find(|nonce| roll_crit(&nonce.to_le_bytes())).unwrap();
+        let plain = (0u64..).find(|nonce| !roll_crit(&nonce.to_le_bytes())).unwrap();
+        assert!(roll_crit(&crit.to_le_bytes()));
+        assert!(!roll_crit(&plain.to_le_bytes()));
+        assert_eq!(crit_damage(7, &crit.to_le_bytes()), 7 * CRIT_MULTIPLIER);
+        assert_eq!(crit_damage(7, &plain.to_le_bytes()), 7);
+    }
+
+    #[test]
+    fn dodge_chance_grows_with_agility_up_to_the_cap() {
+        assert_eq!(dodge_chance(0), 0);
+        assert_eq!(dodge_chance(DODGE_AGILITY_PER_PERCENT), 1);
+        for agility in 0..u8::MAX {
+            assert!(dodge_chance(agility + 1) >= dodge_chance(agility));
+        }
+        assert_eq!(dodge_chance(u8::MAX), MAX_DODGE_PERCENT);
+    }
+
+    #[test]
+    fn scaled_damage_adds_half_the_level_and_saturates() {
+        assert_eq!(scaled_damage(10, 1), 10);
+        assert_eq!(scaled_damage(10, 2), 11);
+        assert_eq!(scaled_damage(10, 50), 35);
+        assert_eq!(scaled_damage(250, 100), u8::MAX);
+    }
+
+    #[test]
+    fn stat_damage_subtracts_defense_down_to_min_damage() {
+        assert_eq!(stat_damage(10, 5), 5);
+        assert_eq!(stat_damage(10, 9), 1);
+        assert_eq!(stat_damage(10, 10), MIN_DAMAGE);
+        assert_eq!(stat_damage(0, u8::MAX), MIN_DAMAGE);
+    }
+
+    fn entry(weight: u16) -> LootEntry {
+        LootEntry {
+            mint: Pubkey::new_unique(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn pick_loot_never_picks_zero_weight_entries() {
+        let entries = [entry(0), entry(3), entry(0)];
+        for nonce in 0u64..200 {
+            let pick = pick_loot(&nonce.to_le_bytes(), &entries);
+            assert_eq!(pick, Some(entries[1].mint));
+        }
+        assert_eq!(pick_loot(b"seed", &[entry(0), entry(0)]), None);
+        assert_eq!(pick_loot(b"seed", &[]), None);
+    }
+
+    #[test]
+    fn pick_loot_follows_the_weights() {
+        let entries = [entry(1), entry(3)];
+        let heavy = (0u64..4_000)
+            .filter(|nonce| pick_loot(&nonce.to_le_bytes(), &entries) == Some(entries[1].mint))
+            .count();
+        // Expect 3,000; well outside this band would mean a biased roll.
+        assert!((2_800..3_200).contains(&heavy), "heavy entry picked {heavy} times");
+    }
+
+    #[test]
+    fn effective_damage_lets_armor_absorb_a_weak_hit_entirely() {
+        assert_eq!(effective_damage(MIN_DAMAGE, 1), 0);
+        assert_eq!(effective_damage(5, 5), 0);
+        assert_eq!(effective_damage(5, u8::MAX), 0);
+    }
+
+    #[test]
+    fn effective_damage_subtracts_armor_from_a_stronger_hit() {
+        assert_eq!(effective_damage(12, 5), 7);
+        assert_eq!(effective_damage(12, 0), 12);
+        // Defense floors at MIN_DAMAGE first; armor then applies to what's left.
+        assert_eq!(effective_damage(stat_damage(3, 50), 0), MIN_DAMAGE);
+        assert_eq!(effective_damage(stat_damage(3, 50), 1), 0);
+    }
+
+    #[test]
+    fn apply_damage_takes_hp_without_defeating_a_survivor() {
+        let mut combat = combat_state().with_hp(30, 100);
+        assert!(!apply_damage(&mut combat, 29, 50, None).unwrap());
+        assert_eq!(combat.hp, 1);
+        assert!(!combat.defeated);
+        assert_eq!(combat.defeated_at, 0);
+    }
+
+    #[test]
+    fn apply_damage_killing_blow_floors_hp_and_records_the_defeat() {
+        let mut combat = combat_state().with_hp(30, 100);
+        let attacker = Pubkey::new_unique();
+        assert!(apply_damage(&mut combat, 200, 50, Some(attacker)).unwrap());
+        assert_eq!(combat.hp, 0);
+        assert!(combat.defeated);
+        assert_eq!(combat.defeated_at, 50);
+        assert_eq!(combat.defeated_by, Some(attacker));
+
+        let mut exact = combat_state().with_hp(30, 100);
+        assert!(apply_damage(&mut exact, 30, 60, None).unwrap());
+        assert_eq!(exact.hp, 0);
+        // A defeated player can't be hit again.
+        assert!(apply_damage(&mut exact, 1, 61, None).is_err());
+        assert_eq!(exact.defeated_at, 60);
+    }
+
+    #[test]
+    fn regenerate_adds_a_point_per_interval_and_carries_the_remainder() {
+        let mut last_update = 100;
+        assert_eq!(regenerate(10, 50, &mut last_update, 100 + 3 * 60 + 59, 60), 13);
+        // The 59 leftover seconds still count toward the next point.
+        assert_eq!(last_update, 100 + 3 * 60);
+        assert_eq!(regenerate(13, 50, &mut last_update, 100 + 4 * 60, 60), 14);
+        assert_eq!(last_update, 100 + 4 * 60);
+    }
+
+    #[test]
+    fn regenerate_caps_at_max_and_survives_huge_gaps() {
+        let mut last_update = 0;
+        assert_eq!(regenerate(10, 50, &mut last_update, i64::MAX, 60), 50);
+        assert_eq!(last_update, i64::MAX);
+        // A clock that runs backwards adds nothing.
+        let mut last_update = 500;
+        assert_eq!(regenerate(10, 50, &mut last_update, 100, 60), 10);
+        assert_eq!(last_update, 500);
+    }
+
+    #[test]
+    fn regen_stamina_refills_toward_max_stamina() {
+        let mut combat = combat_state().with_stamina(MAX_STAMINA - 3 * ATTACK_STAMINA_COST, 1_000);
+        combat.regen_stamina(1_000 + 5 * STAMINA_REGEN_SECS);
+        assert_eq!(combat.stamina, MAX_STAMINA - 3 * ATTACK_STAMINA_COST + 5);
+        combat.regen_stamina(1_000 + 10_000 * STAMINA_REGEN_SECS);
+        assert_eq!(combat.stamina, MAX_STAMINA);
+    }
+
+    #[test]
+    fn regen_mana_refills_toward_max_mana() {
+        let mut combat = combat_state().with_mana(0, 0);
+        combat.regen_mana(7 * MANA_REGEN_SECS + 1);
+        assert_eq!(combat.mana, 7);
+        assert_eq!(combat.last_mana_update, 7 * MANA_REGEN_SECS);
+        combat.regen_mana(1_000 * MANA_REGEN_SECS);
+        assert_eq!(combat.mana, MAX_MANA);
+    }
+
+    #[test]
+    fn spend_mana_counts_regenerated_mana() {
+        // 19 mana plus one point regenerated covers a heal exactly.
+        let mut combat = combat_state().with_mana(HEAL_MANA_COST - 1, 0);
+        combat.spend_mana(HEAL_MANA_COST, MANA_REGEN_SECS).unwrap();
+        assert_eq!(combat.mana, 0);
+    }
+
+    #[test]
+    fn spend_mana_rejects_a_cost_above_the_pool() {
+        let mut combat = combat_state().with_mana(HEAL_MANA_COST - 1, 0);
+        assert!(combat.spend_mana(HEAL_MANA_COST, MANA_REGEN_SECS - 1).is_err());
+        assert_eq!(combat.mana, HEAL_MANA_COST - 1);
+    }
+
+    #[test]
+    fn skill_mana_cost_knows_only_registered_skills() {
+        assert_eq!(skill_mana_cost(SKILL_FIREBALL).unwrap(), FIREBALL_MANA_COST);
+        assert!(skill_mana_cost(SKILL_FIREBALL + 1).is_err());
+        assert!(skill_mana_cost(u8::MAX).is_err());
+    }
+
+    #[test]
+    fn skill_bits_are_distinct_and_out_of_range_ids_have_none() {
+        let mut skills = 0u32;
+        for skill_id in 0..32 {
+            assert_eq!(skills & skill_bit(skill_id), 0);
+            skills |= skill_bit(skill_id);
+        }
+        assert_eq!(skills, u32::MAX);
+        assert_eq!(skill_bit(32), 0);
+        assert_eq!(skill_bit(u8::MAX), 0);
+    }
+
+    #[test]
+    fn tick_poison_deals_stack_damage_per_full_tick() {
+        let mut combat = combat_state().with_hp(100, 100).with_poison(3, 0, 1_000);
+        assert!(!combat.tick_poison(2 * POISON_TICK_SECS + 1).unwrap());
+        assert_eq!(combat.hp, 100 - 2 * 3 * POISON_DAMAGE_PER_STACK as u16);
+        // The partial tick carries over to the next call.
+        assert_eq!(combat.last_poison_tick, 2 * POISON_TICK_SECS);
+        assert_eq!(combat.poison_stacks, 3);
+    }
+
+    #[test]
+    fn tick_poison_stops_at_expiry_and_clears_the_stacks() {
+        let mut combat = combat_state().with_hp(100, 100).with_poison(2, 0, 3 * POISON_TICK_SECS);
+        assert!(!combat.tick_poison(50 * POISON_TICK_SECS).unwrap());
+        assert_eq!(combat.hp, 100 - 3 * 2 * POISON_DAMAGE_PER_STACK as u16);
+        assert_eq!(combat.poison_stacks, 0);
+        // Cured, so later ticks do nothing.
+        assert!(!combat.tick_poison(100 * POISON_TICK_SECS).unwrap());
+        assert_eq!(combat.hp, 100 - 3 * 2 * POISON_DAMAGE_PER_STACK as u16);
+    }
+
+    #[test]
+    fn tick_poison_can_be_the_killing_blow() {
+        let mut combat = combat_state().with_hp(2, 2).with_poison(5, 0, 1_000);
+        assert!(combat.tick_poison(POISON_TICK_SECS).unwrap());
+        assert_eq!(combat.hp, 0);
+        assert!(combat.defeated);
+        assert_eq!(combat.defeated_at, POISON_TICK_SECS);
+        // A defeated player takes no further poison.
+        assert!(!combat.tick_poison(2 * POISON_TICK_SECS).unwrap());
+    }
+
+    #[test]
+    fn cranking_poison_more_often_deals_the_same_damage() {
+        let until = 20 * POISON_TICK_SECS;
+        let mut cranked = combat_state().with_hp(500, 500).with_poison(4, 0, until);
+        let mut idle = combat_state().with_hp(500, 500).with_poison(4, 0, until);
+        for now in 0..=until + POISON_TICK_SECS {
+            cranked.tick_poison(now).unwrap();
+        }
+        idle.tick_poison(until + POISON_TICK_SECS).unwrap();
+        assert_eq!(cranked.hp, idle.hp);
+        assert_eq!(idle.hp, 500 - 20 * 4 * POISON_DAMAGE_PER_STACK as u16);
+        assert_eq!((cranked.poison_stacks, idle.poison_stacks), (0, 0));
+    }
+
+    fn effect(kind: u8, magnitude: i16, expires_at_slot: u64) -> ActiveEffect {
+        ActiveEffect {
+            kind,
+            magnitude,
+            expires_at_slot,
+        }
+    }
+
+    #[test]
+    fn effects_expire_exactly_at_their_expiry_slot() {
+        let buff = effect(EFFECT_ATTACK, 5, 100);
+        assert!(buff.is_active(0));
+        assert!(buff.is_active(99));
+        assert!(!buff.is_active(100));
+        assert!(!ActiveEffect::default().is_active(0));
+    }
+
+    #[test]
+    fn effect_total_sums_active_effects_of_a_kind() {
+        let combat = combat_state().with_effects([
+            effect(EFFECT_ATTACK, 5, 100),
+            effect(EFFECT_ATTACK, -2, 50),
+            effect(EFFECT_DEFENSE, 7, 100),
+            effect(EFFECT_ATTACK, i16::MAX, 10),
+        ]);
+        assert_eq!(combat.effect_total(EFFECT_ATTACK, 49), 3);
+        assert_eq!(combat.effect_total(EFFECT_ATTACK, 50), 5);
+        assert_eq!(combat.effect_total(EFFECT_ATTACK, 100), 0);
+        assert_eq!(combat.effect_total(EFFECT_DEFENSE, 99), 7);
+        // Stacked buffs saturate instead of overflowing.
+        assert_eq!(combat.effect_total(EFFECT_ATTACK, 9), i16::MAX);
+    }
+
+    #[test]
+    fn clear_expired_effects_frees_only_expired_slots() {
+        let mut combat = combat_state().with_effects([
+            effect(EFFECT_ATTACK, 5, 100),
+            effect(EFFECT_DEFENSE, 3, 50),
+            ActiveEffect::default(),
+            effect(EFFECT_ATTACK, 1, 51),
+        ]);
+        combat.clear_expired_effects(50);
+        let expiries: Vec<u64> = combat.effects.iter().map(|e| e.expires_at_slot).collect();
+        assert_eq!(expiries, vec![100, 0, 0, 51]);
+        assert_eq!(combat.effects[1].magnitude, 0);
+        assert_eq!(combat.effect_total(EFFECT_ATTACK, 50), 6);
+    }
+
+    #[test]
+    fn restore_hp_heals_up_to_max_hp() {
+        let mut combat = combat_state().with_hp(40, 100);
+        assert_eq!(combat.restore_hp(25), 25);
+        assert_eq!(combat.hp, 65);
+        assert_eq!(combat.restore_hp(u16::MAX), 35);
+        assert_eq!(combat.hp, 100);
+        // At full health nothing is restored.
+        assert_eq!(combat.restore_hp(1), 0);
+        assert_eq!(combat.hp, 100);
+    }
+
+    #[test]
+    fn revive_restores_a_defeated_player() {
+        let mut combat = combat_state().with_hp(30, 30).with_poison(3, 0, 1_000);
+        combat.death_count = 2;
+        assert!(apply_damage(&mut combat, 30, 500, None).unwrap());
+
+        combat.revive(900);
+        assert_eq!(combat.hp, combat.max_hp);
+        assert!(!combat.defeated);
+        assert_eq!(combat.poison_stacks, 0);
+        assert_eq!(combat.death_count, 3);
+        assert_eq!(combat.last_active, 900);
+        // Alive again, so it can take damage.
+        assert!(!apply_damage(&mut combat, 1, 901, None).unwrap());
+    }
+
+    #[test]
+    fn respawn_is_ready_exactly_one_cooldown_after_the_defeat() {
+        assert_eq!(respawn_ready_at(1_000), 1_000 + RESPAWN_COOLDOWN_SECS);
+        assert_eq!(respawn_ready_at(i64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn attack_cooldown_allows_an_attack_once_it_has_elapsed() {
+        assert!(check_attack_cooldown(1_000, 1_002, 3).is_err());
+        assert!(check_attack_cooldown(1_000, 1_003, 3).is_ok());
+        assert!(check_attack_cooldown(1_000, 5_000, 3).is_ok());
+        // A fresh combat account has never attacked.
+        assert!(check_attack_cooldown(0, 1_000, 3).is_ok());
+        assert!(check_attack_cooldown(1_000, 1_000, 0).is_ok());
+        assert!(check_attack_cooldown(1_000, 1_003, 4).is_err());
+    }
 }
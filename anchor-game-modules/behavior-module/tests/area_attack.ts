@@ -0,0 +1,56 @@
+import { expect } from "chai";
+import { configPda, expectError, newPlayer, program, TestPlayer } from "./helpers";
+
+const targetAccounts = (targets: TestPlayer[]) =>
+  targets.flatMap((target) => [
+    { pubkey: target.player, isSigner: false, isWritable: false },
+    { pubkey: target.combat, isSigner: false, isWritable: true },
+  ]);
+
+describe("area attack", () => {
+  it("hits every target with damage derived from the attacker's stats", async () => {
+    const attacker = await newPlayer("sweeper");
+    const targets = [await newPlayer("first"), await newPlayer("second"), await newPlayer("third")];
+    const before = await Promise.all(
+      targets.map((target) => program.account.combatState.fetch(target.combat))
+    );
+
+    await program.methods
+      .areaAttack()
+      .accounts({
+        attacker: attacker.player,
+        attackerCombat: attacker.combat,
+        config: configPda,
+        owner: attacker.owner.publicKey,
+      })
+      .remainingAccounts(targetAccounts(targets))
+      .signers([attacker.owner])
+      .rpc();
+
+    for (const [i, target] of targets.entries()) {
+      const after = await program.account.combatState.fetch(target.combat);
+      expect(after.hp).to.be.lessThan(before[i].hp);
+    }
+    const attackerCombat = await program.account.combatState.fetch(attacker.combat);
+    expect(attackerCombat.lastAttackTs.toNumber()).to.be.greaterThan(0);
+  });
+
+  it("rejects targets that don't come in pairs", async () => {
+    const attacker = await newPlayer("fumbler");
+    const target = await newPlayer("bystander");
+    await expectError(
+      program.methods
+        .areaAttack()
+        .accounts({
+          attacker: attacker.player,
+          attackerCombat: attacker.combat,
+          config: configPda,
+          owner: attacker.owner.publicKey,
+        })
+        .remainingAccounts(targetAccounts([target]).slice(1))
+        .signers([attacker.owner])
+        .rpc(),
+      "AoeAccountsMismatch"
+    );
+  });
+});
@@ -0,0 +1,167 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import {
+  combatAuthorityPda,
+  expectError,
+  inventoryPda,
+  newPlayer,
+  pda,
+  program,
+  stateProgram,
+  TestPlayer,
+} from "./helpers";
+
+const battlePda = (challenger: TestPlayer, opponent: TestPlayer) =>
+  pda(
+    [Buffer.from("battle"), challenger.player.toBuffer(), opponent.player.toBuffer()],
+    program.programId
+  );
+
+async function challenge(challenger: TestPlayer, opponent: TestPlayer): Promise<PublicKey> {
+  const battle = battlePda(challenger, opponent);
+  await program.methods
+    .challenge()
+    .accounts({
+      challenger: challenger.player,
+      challengerCombat: challenger.combat,
+      opponent: opponent.player,
+      opponentCombat: opponent.combat,
+      battle,
+      owner: challenger.owner.publicKey,
+    })
+    .signers([challenger.owner])
+    .rpc();
+  return battle;
+}
+
+async function accept(battle: PublicKey, challenger: TestPlayer, opponent: TestPlayer) {
+  await program.methods
+    .acceptChallenge()
+    .accounts({
+      battle,
+      challenger: challenger.player,
+      opponent: opponent.player,
+      challengerCombat: challenger.combat,
+      opponentCombat: opponent.combat,
+      combatAuthority: combatAuthorityPda,
+      stateProgram: stateProgram.programId,
+      owner: opponent.owner.publicKey,
+    })
+    .signers([opponent.owner])
+    .rpc();
+}
+
+const turnAccounts = (
+  battle: PublicKey,
+  challenger: TestPlayer,
+  opponent: TestPlayer,
+  signer: TestPlayer
+) => ({
+  battle,
+  challenger: challenger.player,
+  challengerCombat: challenger.combat,
+  opponent: opponent.player,
+  opponentCombat: opponent.combat,
+  combatAuthority: combatAuthorityPda,
+  stateProgram: stateProgram.programId,
+  owner: signer.owner.publicKey,
+});
+
+describe("battle", () => {
+  it("runs challenge, accept and a turn from each side", async () => {
+    const challenger = await newPlayer("duelist-a");
+    const opponent = await newPlayer("duelist-b");
+    const battle = await challenge(challenger, opponent);
+    await accept(battle, challenger, opponent);
+
+    let state = await program.account.battle.fetch(battle);
+    expect(state.status).to.deep.equal({ active: {} });
+    expect(state.turn.toBase58()).to.equal(challenger.player.toBase58());
+    for (const combat of [challenger.combat, opponent.combat]) {
+      expect((await program.account.combatState.fetch(combat)).inBattle).to.equal(true);
+    }
+
+    // The opponent can't move out of turn.
+    await expectError(
+      program.methods
+        .takeTurn({ attack: {} })
+        .accounts(turnAccounts(battle, challenger, opponent, opponent))
+        .signers([opponent.owner])
+        .rpc(),
+      "NotYourTurn"
+    );
+
+    const hpBefore = (await program.account.combatState.fetch(opponent.combat)).hp;
+    await program.methods
+      .takeTurn({ attack: {} })
+      .accounts(turnAccounts(battle, challenger, opponent, challenger))
+      .signers([challenger.owner])
+      .rpc();
+    expect((await program.account.combatState.fetch(opponent.combat)).hp).to.be.lessThan(hpBefore);
+    state = await program.account.battle.fetch(battle);
+    expect(state.turn.toBase58()).to.equal(opponent.player.toBase58());
+
+    await program.methods
+      .takeTurn({ defend: {} })
+      .accounts(turnAccounts(battle, challenger, opponent, opponent))
+      .signers([opponent.owner])
+      .rpc();
+    state = await program.account.battle.fetch(battle);
+    expect(state.turn.toBase58()).to.equal(challenger.player.toBase58());
+  });
+
+  it("keeps players locked when an unrelated pending challenge is forfeited", async () => {
+    const locked = await newPlayer("locked-a");
+    const rival = await newPlayer("locked-b");
+    const bystander = await newPlayer("locked-c");
+    const throwaway = await challenge(bystander, locked);
+    const battle = await challenge(locked, rival);
+    await accept(battle, locked, rival);
+
+    // A player already locked into a battle can't be challenged.
+    await expectError(challenge(bystander, rival), "InBattle");
+
+    await program.methods
+      .forfeit()
+      .accounts(turnAccounts(throwaway, bystander, locked, locked))
+      .signers([locked.owner])
+      .rpc();
+    const abandoned = await program.account.battle.fetch(throwaway);
+    expect(abandoned.status).to.deep.equal({ finished: {} });
+    expect(abandoned.winner).to.equal(null);
+    expect((await program.account.combatState.fetch(locked.combat)).inBattle).to.equal(true);
+  });
+
+  it("locks both players against close_player until the battle ends", async () => {
+    const challenger = await newPlayer("closer-a");
+    const opponent = await newPlayer("closer-b");
+    const battle = await challenge(challenger, opponent);
+    await accept(battle, challenger, opponent);
+    for (const player of [challenger.player, opponent.player]) {
+      expect((await stateProgram.account.playerState.fetch(player)).locked).to.equal(true);
+    }
+
+    const close = () =>
+      stateProgram.methods
+        .closePlayer()
+        .accounts({
+          player: opponent.player,
+          inventory: inventoryPda(opponent.player),
+          owner: opponent.owner.publicKey,
+        })
+        .signers([opponent.owner])
+        .rpc();
+    await expectError(close(), "PlayerLocked");
+
+    await program.methods
+      .forfeit()
+      .accounts(turnAccounts(battle, challenger, opponent, opponent))
+      .signers([opponent.owner])
+      .rpc();
+    for (const player of [challenger.player, opponent.player]) {
+      expect((await stateProgram.account.playerState.fetch(player)).locked).to.equal(false);
+    }
+    await close();
+    expect(await stateProgram.provider.connection.getAccountInfo(opponent.player)).to.equal(null);
+  });
+});
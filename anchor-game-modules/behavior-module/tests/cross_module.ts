@@ -0,0 +1,71 @@
+import { BN } from "@coral-xyz/anchor";
+import { expect } from "chai";
+import {
+  attackAccounts,
+  configPda,
+  expectError,
+  newPlayer,
+  program,
+  provider,
+  stateProgram,
+  TestPlayer,
+} from "./helpers";
+
+const attack = (attacker: TestPlayer, defender: TestPlayer) =>
+  program.methods
+    .attack(false)
+    .accounts(attackAccounts(attacker, defender))
+    .signers([attacker.owner])
+    .rpc();
+
+const setAttackCooldown = (secs: number) =>
+  stateProgram.methods
+    .updateConfig({
+      admin: provider.wallet.publicKey,
+      maxLevel: 100,
+      baseHp: 100,
+      xpMultiplier: 100,
+      attackCooldownSecs: new BN(secs),
+    })
+    .accounts({ config: configPda, admin: provider.wallet.publicKey })
+    .rpc();
+
+// Players are registered by state_module and fought over by behavior_module, which reads the
+// same PlayerState account through game-common.
+describe("cross-module player state", () => {
+  it("enters combat with the game's base hp", async () => {
+    const { player, combat } = await newPlayer("crossover");
+    const config = await stateProgram.account.gameConfig.fetch(configPda);
+    const combatState = await program.account.combatState.fetch(combat);
+    expect(combatState.player).to.deep.equal(player);
+    expect(combatState.maxHp).to.equal(config.baseHp);
+    expect(combatState.hp).to.equal(config.baseHp);
+  });
+
+  it("attacks between two state_module players", async () => {
+    const attacker = await newPlayer("striker");
+    const defender = await newPlayer("target");
+    const before = await program.account.combatState.fetch(defender.combat);
+
+    await attack(attacker, defender);
+
+    // A low-agility defender can still dodge, so only the attacker's side is certain.
+    const after = await program.account.combatState.fetch(defender.combat);
+    expect(after.hp).to.be.at.most(before.hp);
+    const attackerCombat = await program.account.combatState.fetch(attacker.combat);
+    expect(attackerCombat.attackNonce.toNumber()).to.equal(1);
+    expect(attackerCombat.lastAttackTs.toNumber()).to.be.greaterThan(0);
+  });
+
+  it("enforces the configured attack cooldown", async () => {
+    const attacker = await newPlayer("hasty");
+    const defender = await newPlayer("patient");
+    await setAttackCooldown(60);
+    try {
+      await attack(attacker, defender);
+      await expectError(attack(attacker, defender), "AttackOnCooldown");
+    } finally {
+      await setAttackCooldown(0);
+    }
+  });
+});
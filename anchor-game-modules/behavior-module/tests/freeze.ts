@@ -0,0 +1,37 @@
+import { expect } from "chai";
+import {
+  attackAccounts,
+  configPda,
+  expectError,
+  newPlayer,
+  program,
+  stateProgram,
+} from "./helpers";
+
+describe("frozen players", () => {
+  it("can't attack until the admin unfreezes them", async () => {
+    const attacker = await newPlayer("cheater");
+    const defender = await newPlayer("bystander");
+    const admin = stateProgram.provider.publicKey!;
+    const moderate = (method: "freezePlayer" | "unfreezePlayer") =>
+      stateProgram.methods[method]()
+        .accounts({ player: attacker.player, config: configPda, admin })
+        .rpc();
+    const attack = () =>
+      program.methods
+        .attack(false)
+        .accounts(attackAccounts(attacker, defender))
+        .signers([attacker.owner])
+        .rpc();
+
+    await moderate("freezePlayer");
+    await expectError(attack(), "PlayerFrozen");
+    let attackerCombat = await program.account.combatState.fetch(attacker.combat);
+    expect(attackerCombat.attackNonce.toNumber()).to.equal(0);
+
+    await moderate("unfreezePlayer");
+    await attack();
+    attackerCombat = await program.account.combatState.fetch(attacker.combat);
+    expect(attackerCombat.attackNonce.toNumber()).to.equal(1);
+  });
+});
@@ -0,0 +1,168 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Idl, Program } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SYSVAR_SLOT_HASHES_PUBKEY,
+} from "@solana/web3.js";
+import { BehaviorModule } from "../target/types/behavior_module";
+import assetIdl from "../../asset-module/target/idl/asset_module.json";
+import stateIdl from "../../state-module/target/idl/state_module.json";
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+
+export const program = anchor.workspace.BehaviorModule as Program<BehaviorModule>;
+export const stateProgram = new Program(
+  stateIdl as Idl,
+  new PublicKey(stateIdl.metadata.address),
+  provider
+);
+export const assetProgram = new Program(
+  assetIdl as Idl,
+  new PublicKey(assetIdl.metadata.address),
+  provider
+);
+
+export const pda = (seeds: (Buffer | Uint8Array)[], programId: PublicKey) =>
+  PublicKey.findProgramAddressSync(seeds, programId)[0];
+
+export const configPda = pda([Buffer.from("config")], stateProgram.programId);
+export const stateProgramDataPda = PublicKey.findProgramAddressSync(
+  [stateProgram.programId.toBuffer()],
+  new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+)[0];
+export const registryPda = pda([Buffer.from("registry")], stateProgram.programId);
+export const leaderboardPda = pda([Buffer.from("leaderboard")], stateProgram.programId);
+export const combatAuthorityPda = pda([Buffer.from("combat_authority")], program.programId);
+
+export const playerPda = (owner: PublicKey) =>
+  pda([Buffer.from("player"), owner.toBuffer()], stateProgram.programId);
+export const inventoryPda = (player: PublicKey) =>
+  pda([Buffer.from("inventory"), player.toBuffer()], stateProgram.programId);
+export const combatPda = (player: PublicKey) =>
+  pda([Buffer.from("combat"), player.toBuffer()], program.programId);
+export const matchPda = (challenger: PublicKey, opponent: PublicKey) =>
+  pda([Buffer.from("match"), challenger.toBuffer(), opponent.toBuffer()], program.programId);
+export const stakeEscrowPda = (combat: PublicKey, mint: PublicKey) =>
+  pda([Buffer.from("stake_escrow"), combat.toBuffer(), mint.toBuffer()], program.programId);
+
+export type TestPlayer = {
+  owner: Keypair;
+  player: PublicKey;
+  combat: PublicKey;
+};
+
+export async function fundedKeypair(sol = 10): Promise<Keypair> {
+  const keypair = Keypair.generate();
+  const signature = await provider.connection.requestAirdrop(
+    keypair.publicKey,
+    sol * LAMPORTS_PER_SOL
+  );
+  await provider.connection.confirmTransaction(signature);
+  return keypair;
+}
+
+// Creates the shared state_module accounts the first time any test file needs them. The
+// provider wallet is state_module's upgrade authority (see Anchor.toml) and the game admin,
+// and attacks have no cooldown unless a test sets one.
+export async function ensureGame() {
+  if (await provider.connection.getAccountInfo(configPda)) {
+    return;
+  }
+  const admin = provider.wallet.publicKey;
+  await stateProgram.methods
+    .initializeConfig({
+      admin,
+      maxLevel: 100,
+      baseHp: 100,
+      xpMultiplier: 100,
+      attackCooldownSecs: new BN(0),
+    })
+    .accounts({
+      config: configPda,
+      authority: admin,
+      program: stateProgram.programId,
+      programData: stateProgramDataPda,
+    })
+    .rpc();
+  await stateProgram.methods
+    .initializeRegistry()
+    .accounts({ registry: registryPda, payer: admin })
+    .rpc();
+  await stateProgram.methods
+    .initializeLeaderboard()
+    .accounts({ leaderboard: leaderboardPda, payer: admin })
+    .rpc();
+}
+
+// Registers a player under a fresh wallet and enters it into combat.
+export async function newPlayer(name: string): Promise<TestPlayer> {
+  await ensureGame();
+  const owner = await fundedKeypair();
+  const player = playerPda(owner.publicKey);
+  await stateProgram.methods
+    .registerPlayer(name)
+    .accounts({
+      player,
+      config: configPda,
+      registry: registryPda,
+      authority: owner.publicKey,
+    })
+    .signers([owner])
+    .rpc();
+  const combat = combatPda(player);
+  await program.methods
+    .enterCombat()
+    .accounts({ player, config: configPda, combat, owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+  return { owner, player, combat };
+}
+
+// Accounts for a plain attack: no weapon, no loot drop and no staked loot.
+export const attackAccounts = (attacker: TestPlayer, defender: TestPlayer) => ({
+  attacker: attacker.player,
+  attackerCombat: attacker.combat,
+  defender: defender.player,
+  defenderCombat: defender.combat,
+  config: configPda,
+  leaderboard: leaderboardPda,
+  combatAuthority: combatAuthorityPda,
+  slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+  stateProgram: stateProgram.programId,
+  owner: attacker.owner.publicKey,
+  lootTable: null,
+  lootMint: null,
+  lootMintConfig: null,
+  lootMintAuthority: null,
+  lootItemMetadata: null,
+  lootItemType: null,
+  lootTokenAccount: null,
+  assetProgram: null,
+  tokenProgram: null,
+  associatedTokenProgram: null,
+  systemProgram: null,
+  rent: null,
+  weaponMetadata: null,
+  weaponTokenAccount: null,
+  armorMetadata: null,
+  armorTokenAccount: null,
+  stakedLootAccount: null,
+  lootRecipient: null,
+});
+
+// Resolves once `promise` fails with the named program error.
+export async function expectError(promise: Promise<unknown>, code: string) {
+  try {
+    await promise;
+  } catch (err) {
+    const actual = err instanceof anchor.AnchorError ? err.error.errorCode.code : String(err);
+    if (!actual.includes(code)) {
+      throw new Error(`expected ${code}, got ${actual}`);
+    }
+    return;
+  }
+  throw new Error(`expected ${code}, but the transaction succeeded`);
+}
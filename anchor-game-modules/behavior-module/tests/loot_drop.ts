@@ -0,0 +1,122 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
+import {
+  assetProgram,
+  attackAccounts,
+  configPda,
+  ensureGame,
+  newPlayer,
+  pda,
+  program,
+  provider,
+  stateProgram,
+  TestPlayer,
+} from "./helpers";
+
+const TYPE_ID = 1;
+const lootTablePda = pda([Buffer.from("loot_table")], program.programId);
+const assetPda = (seed: string, mint: PublicKey) =>
+  pda([Buffer.from(seed), mint.toBuffer()], assetProgram.programId);
+const mintAuthorityPda = pda([Buffer.from("mint_authority")], assetProgram.programId);
+const itemTypePda = pda([Buffer.from("item_type"), Buffer.from([TYPE_ID])], assetProgram.programId);
+
+describe("loot drops", () => {
+  const admin = provider.wallet.publicKey;
+  let lootMint: PublicKey;
+
+  const setBaseHp = (baseHp: number) =>
+    stateProgram.methods
+      .updateConfig({
+        admin,
+        maxLevel: 100,
+        baseHp,
+        xpMultiplier: 100,
+        attackCooldownSecs: new BN(0),
+      })
+      .accounts({ config: configPda, admin })
+      .rpc();
+
+  // A loot-only item that the loot table always picks.
+  before(async () => {
+    await ensureGame();
+    await assetProgram.methods
+      .registerItemType(TYPE_ID, "loot", new BN(1_000_000))
+      .accounts({ itemType: itemTypePda, config: configPda, admin })
+      .rpc();
+    const mint = Keypair.generate();
+    await assetProgram.methods
+      .createItem({
+        decimals: 0,
+        maxSupply: new BN(10),
+        name: "trophy",
+        itemType: TYPE_ID,
+        rarity: 0,
+        statBonus: 0,
+        royaltyRecipient: admin,
+        royaltyBasisPoints: 0,
+        lootOnly: true,
+      })
+      .accounts({
+        admin,
+        config: configPda,
+        itemType: itemTypePda,
+        mint: mint.publicKey,
+        mintAuthority: mintAuthorityPda,
+        mintConfig: assetPda("mint_config", mint.publicKey),
+        itemMetadata: assetPda("item_metadata", mint.publicKey),
+        royaltyConfig: assetPda("royalty", mint.publicKey),
+      })
+      .signers([mint])
+      .rpc();
+    lootMint = mint.publicKey;
+    await program.methods
+      .setLootTable([{ mint: lootMint, weight: 1 }])
+      .accounts({ lootTable: lootTablePda, config: configPda, admin })
+      .rpc();
+  });
+
+  const lootAccounts = (attacker: TestPlayer, defender: TestPlayer) => ({
+    ...attackAccounts(attacker, defender),
+    lootTable: lootTablePda,
+    lootMint,
+    lootMintConfig: assetPda("mint_config", lootMint),
+    lootMintAuthority: mintAuthorityPda,
+    lootItemMetadata: assetPda("item_metadata", lootMint),
+    lootItemType: itemTypePda,
+    lootTokenAccount: getAssociatedTokenAddressSync(lootMint, attacker.owner.publicKey),
+    assetProgram: assetProgram.programId,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+    rent: SYSVAR_RENT_PUBKEY,
+  });
+
+  it("mints the loot-only item to the attacker on a kill", async () => {
+    const attacker = await newPlayer("hunter");
+    // Any hit that isn't dodged finishes a defender entering combat with 5 hp.
+    await setBaseHp(5);
+    const defender = await newPlayer("quarry").finally(() => setBaseHp(100));
+
+    while (!(await program.account.combatState.fetch(defender.combat)).defeated) {
+      await program.methods
+        .attack(true)
+        .accounts(lootAccounts(attacker, defender))
+        .signers([attacker.owner])
+        .rpc();
+    }
+    const loot = await getAccount(
+      provider.connection,
+      getAssociatedTokenAddressSync(lootMint, attacker.owner.publicKey)
+    );
+    expect(loot.amount).to.equal(BigInt(1));
+    const config = await assetProgram.account.mintConfig.fetch(assetPda("mint_config", lootMint));
+    expect((config.minted as BN).toNumber()).to.equal(1);
+  });
+});
@@ -0,0 +1,53 @@
+import { expect } from "chai";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  configPda,
+  ensureGame,
+  expectError,
+  fundedKeypair,
+  pda,
+  program,
+  provider,
+} from "./helpers";
+
+const lootTablePda = pda([Buffer.from("loot_table")], program.programId);
+
+describe("loot table", () => {
+  before(ensureGame);
+
+  it("only lets the admin set the loot table", async () => {
+    const stranger = await fundedKeypair();
+    await expectError(
+      program.methods
+        .setLootTable([{ mint: Keypair.generate().publicKey, weight: 1 }])
+        .accounts({ lootTable: lootTablePda, config: configPda, admin: stranger.publicKey })
+        .signers([stranger])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+  });
+
+  it("stores the admin's entries and rejects an all-zero table", async () => {
+    const admin = provider.wallet.publicKey;
+    const mints: PublicKey[] = [Keypair.generate().publicKey, Keypair.generate().publicKey];
+    await program.methods
+      .setLootTable([
+        { mint: mints[0], weight: 3 },
+        { mint: mints[1], weight: 1 },
+      ])
+      .accounts({ lootTable: lootTablePda, config: configPda, admin })
+      .rpc();
+    const table = await program.account.lootTable.fetch(lootTablePda);
+    expect(table.entries.map((entry) => entry.mint.toBase58())).to.deep.equal(
+      mints.map((mint) => mint.toBase58())
+    );
+
+    await expectError(
+      program.methods
+        .setLootTable([{ mint: mints[0], weight: 0 }])
+        .accounts({ lootTable: lootTablePda, config: configPda, admin })
+        .rpc(),
+      "InvalidLootTable"
+    );
+  });
+});
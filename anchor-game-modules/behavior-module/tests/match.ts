@@ -0,0 +1,82 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import { expectError, matchPda, newPlayer, program, provider } from "./helpers";
+
+const WAGER = LAMPORTS_PER_SOL / 10;
+
+describe("match", () => {
+  it("escrows both wagers and holds them until a defeat or the deadline", async () => {
+    const challenger = await newPlayer("duelist");
+    const opponent = await newPlayer("rival");
+    const match = matchPda(challenger.player, opponent.player);
+    const balance = (key: PublicKey) => provider.connection.getBalance(key);
+
+    await program.methods
+      .createMatch(new BN(WAGER))
+      .accounts({
+        challenger: challenger.player,
+        opponent: opponent.player,
+        matchAccount: match,
+        owner: challenger.owner.publicKey,
+      })
+      .signers([challenger.owner])
+      .rpc();
+    const rent = (await balance(match)) - WAGER;
+
+    const opponentBefore = await balance(opponent.owner.publicKey);
+    await program.methods
+      .acceptMatch()
+      .accounts({
+        opponent: opponent.player,
+        matchAccount: match,
+        challengerCombat: challenger.combat,
+        opponentCombat: opponent.combat,
+        owner: opponent.owner.publicKey,
+      })
+      .signers([opponent.owner])
+      .rpc();
+    expect(await balance(match)).to.equal(rent + 2 * WAGER);
+    expect(opponentBefore - (await balance(opponent.owner.publicKey))).to.be.at.least(WAGER);
+    const stored = await program.account.match.fetch(match);
+    expect(stored.accepted).to.equal(true);
+    expect(stored.acceptedAt.toNumber()).to.be.greaterThan(0);
+
+    // Nobody has fallen since the match was accepted, so there is no winner yet.
+    await expectError(
+      program.methods
+        .resolveMatch()
+        .accounts({
+          matchAccount: match,
+          challengerCombat: challenger.combat,
+          opponentCombat: opponent.combat,
+          winner: challenger.owner.publicKey,
+          challengerOwner: challenger.owner.publicKey,
+        })
+        .rpc(),
+      "MatchNotConcluded"
+    );
+    await expectError(
+      program.methods
+        .refundMatch()
+        .accounts({
+          matchAccount: match,
+          challengerCombat: challenger.combat,
+          opponentCombat: opponent.combat,
+          challengerOwner: challenger.owner.publicKey,
+          opponentOwner: opponent.owner.publicKey,
+        })
+        .rpc(),
+      "MatchDeadlineNotReached"
+    );
+    await expectError(
+      program.methods
+        .cancelMatch()
+        .accounts({ matchAccount: match, owner: challenger.owner.publicKey })
+        .signers([challenger.owner])
+        .rpc(),
+      "MatchAlreadyAccepted"
+    );
+    expect(await balance(match)).to.equal(rent + 2 * WAGER);
+  });
+});
@@ -0,0 +1,76 @@
+import { expect } from "chai";
+import {
+  configPda,
+  ensureGame,
+  expectError,
+  newPlayer,
+  pda,
+  program,
+  provider,
+  TestPlayer,
+} from "./helpers";
+
+const queuePda = pda([Buffer.from("match_queue")], program.programId);
+
+const enqueue = ({ owner, player }: TestPlayer) =>
+  program.methods
+    .enqueue()
+    .accounts({ player, queue: queuePda, owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+const dequeue = ({ owner, player }: TestPlayer) =>
+  program.methods
+    .dequeueMatch()
+    .accounts({ player, queue: queuePda, owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+const queued = async () => {
+  const queue = await program.account.matchQueue.fetch(queuePda);
+  return queue.entries.slice(0, queue.len).map((entry) => entry.player.toBase58());
+};
+
+// Only this file uses the shared queue, so it starts out empty.
+describe("match queue", () => {
+  before(async () => {
+    await ensureGame();
+    await program.methods
+      .initMatchQueue()
+      .accounts({ queue: queuePda, config: configPda, admin: provider.wallet.publicKey })
+      .rpc();
+  });
+
+  it("rejects a dequeue with nobody waiting", async () => {
+    const loner = await newPlayer("loner");
+    await expectError(dequeue(loner), "QueueEmpty");
+
+    // Being the only one queued doesn't count either.
+    await enqueue(loner);
+    await expectError(dequeue(loner), "QueueEmpty");
+    await dequeue(await newPlayer("company"));
+    expect(await queued()).to.deep.equal([]);
+  });
+
+  it("rejects queueing twice", async () => {
+    const eager = await newPlayer("eager");
+    await enqueue(eager);
+    await expectError(enqueue(eager), "AlreadyQueued");
+    expect(await queued()).to.deep.equal([eager.player.toBase58()]);
+
+    await dequeue(await newPlayer("taker"));
+    expect(await queued()).to.deep.equal([]);
+  });
+
+  it("pairs same-level players in the order they queued", async () => {
+    const first = await newPlayer("first");
+    const second = await newPlayer("second");
+    await enqueue(first);
+    await enqueue(second);
+
+    await dequeue(await newPlayer("caller"));
+    expect(await queued()).to.deep.equal([second.player.toBase58()]);
+    await dequeue(await newPlayer("latecomer"));
+    expect(await queued()).to.deep.equal([]);
+  });
+});
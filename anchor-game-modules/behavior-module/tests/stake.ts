@@ -0,0 +1,82 @@
+import { expect } from "chai";
+import {
+  createAssociatedTokenAccount,
+  createMint,
+  getAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import {
+  combatAuthorityPda,
+  expectError,
+  newPlayer,
+  program,
+  provider,
+  stakeEscrowPda,
+  TestPlayer,
+} from "./helpers";
+
+describe("staked loot", () => {
+  let staker: TestPlayer;
+  let mint: PublicKey;
+  let wallet: PublicKey;
+  let escrow: PublicKey;
+
+  before(async () => {
+    staker = await newPlayer("staker");
+    const owner = staker.owner;
+    mint = await createMint(provider.connection, owner, owner.publicKey, null, 0);
+    wallet = await createAssociatedTokenAccount(provider.connection, owner, mint, owner.publicKey);
+    await mintTo(provider.connection, owner, mint, wallet, owner, 1);
+    escrow = stakeEscrowPda(staker.combat, mint);
+  });
+
+  const stakeAccounts = () => ({
+    player: staker.player,
+    combat: staker.combat,
+    lootTokenAccount: wallet,
+    lootMint: mint,
+    escrow,
+    combatAuthority: combatAuthorityPda,
+    owner: staker.owner.publicKey,
+  });
+
+  const unstakeAccounts = () => ({
+    player: staker.player,
+    combat: staker.combat,
+    escrow,
+    lootTokenAccount: wallet,
+    combatAuthority: combatAuthorityPda,
+    owner: staker.owner.publicKey,
+  });
+
+  it("moves the staked item into an escrow the owner can't touch", async () => {
+    await program.methods.stakeLoot().accounts(stakeAccounts()).signers([staker.owner]).rpc();
+
+    const held = await getAccount(provider.connection, escrow);
+    expect(held.amount).to.equal(BigInt(1));
+    expect(held.owner.toBase58()).to.equal(combatAuthorityPda.toBase58());
+    expect((await getAccount(provider.connection, wallet)).amount).to.equal(BigInt(0));
+    const combat = await program.account.combatState.fetch(staker.combat);
+    expect(combat.stakedLoot?.toBase58()).to.equal(escrow.toBase58());
+
+    await expectError(
+      program.methods.stakeLoot().accounts(stakeAccounts()).signers([staker.owner]).rpc(),
+      "LootAlreadyStaked"
+    );
+  });
+
+  it("returns the item and closes the escrow on unstake", async () => {
+    await program.methods.unstakeLoot().accounts(unstakeAccounts()).signers([staker.owner]).rpc();
+
+    expect((await getAccount(provider.connection, wallet)).amount).to.equal(BigInt(1));
+    expect(await provider.connection.getAccountInfo(escrow)).to.be.null;
+    const combat = await program.account.combatState.fetch(staker.combat);
+    expect(combat.stakedLoot).to.be.null;
+
+    await expectError(
+      program.methods.unstakeLoot().accounts(unstakeAccounts()).signers([staker.owner]).rpc(),
+      "AccountNotInitialized"
+    );
+  });
+});
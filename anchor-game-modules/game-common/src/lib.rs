@@ -0,0 +1,142 @@
+#![allow(clippy::result_large_err)]
+
+use anchor_lang::prelude::*;
+
+// Player accounts are owned by state_module, so this must match its program id. Owner checks on
+// Account<PlayerState> compare against it, which lets other modules read those accounts directly.
+declare_id!("St4teModu13D3mo1111111111111111111111111111");
+
+// Bump whenever the PlayerState layout changes. New fields go at the end so migrate_player can
+// grow an old account and zero-fill the tail before filling defaults. A removed field needs its
+// own migrate_player step that cuts its bytes out first, as version 12 does for hp and max_hp.
+pub const PLAYER_VERSION: u8 = 12;
+
+// Player accounts live at [PLAYER_SEED, registering wallet], so each wallet registers exactly one.
+pub const PLAYER_SEED: &[u8] = b"player";
+
+// behavior_module signs state_module CPIs with the PDA at [COMBAT_AUTHORITY_SEED] under this id.
+pub const BEHAVIOR_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("BehAviorModu1eDemo11111111111111111111111111");
+pub const COMBAT_AUTHORITY_SEED: &[u8] = b"combat_authority";
+
+// Measured in bytes, since that is what the account space has to hold.
+pub const MAX_NAME_LEN: usize = 32;
+
+pub const MAX_URI_LEN: usize = 200;
+
+#[account]
+#[derive(Default)]
+pub struct PlayerState {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub name: String,
+    pub level: u8,
+    pub bump: u8,
+    pub experience: u64,
+    pub attack_power: u8,
+    pub defense: u8,
+    pub mana: u8,
+    // Set by behavior_module while the player is in a battle; blocks close_player. Wagered
+    // matches don't set it: they settle from CombatState and the stored wallets, which closing
+    // the player leaves in place.
+    pub locked: bool,
+    // PDA seed; stays fixed when ownership is transferred.
+    pub registered_by: Pubkey,
+    pub guild: Option<Pubkey>,
+    pub strength: u8,
+    pub agility: u8,
+    pub vitality: u8,
+    pub unspent_points: u8,
+    pub pending_owner: Option<Pubkey>,
+    pub player_index: u64,
+    pub created_at: i64,
+    pub last_active: i64,
+    pub achievements: u64,
+    // Off-chain profile (avatar, bio); empty when unset.
+    pub metadata_uri: String,
+    // Set by the admin to block every player-driven mutation.
+    pub frozen: bool,
+    pub equipment: EquipmentSlots,
+    pub last_rename_ts: i64,
+    // Soft currency tracked in-program; not an SPL token.
+    pub gold: u64,
+    // Service allowed to set this player's level alongside the game admin.
+    pub controller: Option<Pubkey>,
+}
+
+impl PlayerState {
+    // Upper bound, with both strings at their maximum length.
+    pub const SPACE: usize = 8 // discriminator
+        + 1 // version
+        + 32 // owner
+        + 4 + MAX_NAME_LEN // name
+        + 1 // level
+        + 1 // bump
+        + 8 // experience
+        + 3 // attack_power, defense, mana
+        + 1 // locked
+        + 32 // registered_by
+        + 33 // guild
+        + 4 // strength, agility, vitality, unspent_points
+        + 33 // pending_owner
+        + 8 // player_index
+        + 8 // created_at
+        + 8 // last_active
+        + 8 // achievements
+        + 4 + MAX_URI_LEN // metadata_uri
+        + 1 // frozen
+        + EquipmentSlots::SIZE // equipment
+        + 8 // last_rename_ts
+        + 8 // gold
+        + 33; // controller
+
+    pub fn touch(&mut self) -> Result<()> {
+        self.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    // Accounts are sized to their current strings and resized when either changes.
+    pub fn space(name: &str, metadata_uri: &str) -> usize {
+        Self::SPACE - MAX_NAME_LEN - MAX_URI_LEN + name.len() + metadata_uri.len()
+    }
+}
+
+// Item mint held in each gear slot. Equipped items must also be in the player's inventory.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EquipmentSlots {
+    pub weapon: Option<Pubkey>,
+    pub armor: Option<Pubkey>,
+    pub accessory: Option<Pubkey>,
+}
+
+impl EquipmentSlots {
+    pub const SIZE: usize = 33 * 3;
+
+    pub fn slot_mut(&mut self, slot: SlotKind) -> &mut Option<Pubkey> {
+        match slot {
+            SlotKind::Weapon => &mut self.weapon,
+            SlotKind::Armor => &mut self.armor,
+            SlotKind::Accessory => &mut self.accessory,
+        }
+    }
+
+    pub fn contains(&self, mint: &Pubkey) -> bool {
+        [self.weapon, self.armor, self.accessory].contains(&Some(*mint))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Weapon,
+    Armor,
+    Accessory,
+}
+
+// Offset keeps these clear of each program's own CustomError codes.
+#[error_code(offset = 7000)]
+pub enum PlayerError {
+    #[msg("Player account uses an older layout; call migrate_player first.")]
+    MigrationRequired,
+    #[msg("Player is frozen.")]
+    PlayerFrozen,
+}
@@ -1,43 +1,1832 @@
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token::{Mint, TokenAccount};
+use crate::program::StateModule;
+pub use game_common::{
+    EquipmentSlots, PlayerError, PlayerState, SlotKind, BEHAVIOR_PROGRAM_ID,
+    COMBAT_AUTHORITY_SEED, MAX_NAME_LEN, MAX_URI_LEN, PLAYER_SEED, PLAYER_VERSION,
+};
+
+// Must match game_common::ID, which owner checks on PlayerState use.
+declare_id!("St4teModu13D3mo1111111111111111111111111111");
+
+pub const GUILD_SEED: &[u8] = b"guild";
+pub const MEMBER_SEED: &[u8] = b"member";
+pub const INVENTORY_SEED: &[u8] = b"inventory";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const REGISTRY_SEED: &[u8] = b"registry";
+pub const LEADERBOARD_SEED: &[u8] = b"leaderboard";
+
+// Guild names double as the PDA seed, which is limited to 32 bytes.
+pub const MAX_GUILD_NAME_LEN: usize = 32;
+// Upper bound on the capacity a leader can pick when creating a guild.
+pub const MAX_GUILD_MEMBERS: u16 = 100;
+
+// Inventory space is allocated for the full capacity up front.
+pub const MAX_INVENTORY: usize = 16;
+
+// Hard ceiling for GameConfig::max_level; also bounds the level-up loop.
+pub const MAX_LEVEL: u8 = 100;
+
+pub const LEADERBOARD_SIZE: usize = 10;
 
-declare_id!("St4teModu13D3mo1111111111111111111111111111111111");
+// Keeps register_players_batch within the compute budget.
+pub const MAX_BATCH_REGISTRATIONS: usize = 5;
+
+// One bit per achievement in PlayerState::achievements.
+pub const MAX_ACHIEVEMENTS: u8 = 64;
+
+// Level-1 combat stats written by register_player. Hp isn't one: behavior_module starts each
+// CombatState at GameConfig::base_hp.
+pub const STARTING_ATTACK_POWER: u8 = 10;
+pub const STARTING_DEFENSE: u8 = 5;
+pub const STARTING_MANA: u8 = 50;
+pub const STARTING_ATTRIBUTE: u8 = 5;
+pub const STARTING_STAT_POINTS: u8 = 5;
+
+// Minimum seconds between two renames of the same player.
+pub const RENAME_COOLDOWN_SECS: i64 = 24 * 60 * 60;
+
+// Used when GameConfigParams leaves attack_cooldown_secs unset.
+pub const DEFAULT_ATTACK_COOLDOWN_SECS: i64 = 10;
+
+// First PlayerState version without hp and max_hp.
+const NO_HP_VERSION: u8 = 12;
 
 #[program]
 pub mod state_module {
     use super::*;
     pub fn register_player(ctx: Context<RegisterPlayer>, name: String) -> Result<()> {
-        let player = &mut ctx.accounts.player;
-        player.owner = ctx.accounts.authority.key();
-        player.name = name;
-        player.level = 1;
+        validate_name(&name)?;
+        let player = new_player(
+            ctx.accounts.authority.key(),
+            name,
+            *ctx.bumps.get("player").unwrap(),
+            &mut ctx.accounts.registry,
+        )?;
+        emit!(PlayerRegistered {
+            player: ctx.accounts.player.key(),
+            owner: player.owner,
+            name: player.name.clone(),
+        });
+        ctx.accounts.player.set_inner(player);
+        Ok(())
+    }
+
+    // remaining_accounts holds one (owner wallet, player PDA) pair per name. Every owner signs,
+    // as with register_player, and the authority pays for every account. Any invalid name or
+    // already existing account fails the whole batch.
+    pub fn register_players_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, RegisterPlayersBatch<'info>>,
+        names: Vec<String>,
+    ) -> Result<()> {
+        require!(names.len() <= MAX_BATCH_REGISTRATIONS, CustomError::BatchTooLarge);
+        require!(
+            ctx.remaining_accounts.len() == names.len() * 2,
+            CustomError::BatchAccountsMismatch
+        );
+        let rent = Rent::get()?;
+        for (name, accounts) in names.into_iter().zip(ctx.remaining_accounts.chunks(2)) {
+            let (owner_info, player_info) = (&accounts[0], &accounts[1]);
+            require!(owner_info.is_signer, CustomError::OwnerMustSign);
+            validate_name(&name)?;
+            let (expected, bump) = Pubkey::find_program_address(
+                &[PLAYER_SEED, owner_info.key.as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(expected, *player_info.key, CustomError::InvalidPlayerAccount);
+            require!(
+                player_info.data_is_empty() && *player_info.owner == System::id(),
+                CustomError::PlayerAlreadyExists
+            );
+
+            create_player_account(
+                &ctx.accounts.system_program,
+                &ctx.accounts.authority,
+                player_info,
+                &[PLAYER_SEED, owner_info.key.as_ref(), &[bump]],
+                PlayerState::space(&name, ""),
+                &rent,
+            )?;
+
+            let player = new_player(
+                *owner_info.key,
+                name,
+                bump,
+                &mut ctx.accounts.registry,
+            )?;
+            let mut data = player_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            player.try_serialize(&mut writer)?;
+            emit!(PlayerRegistered {
+                player: *player_info.key,
+                owner: player.owner,
+                name: player.name,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn initialize_registry(ctx: Context<InitializeRegistry>) -> Result<()> {
+        ctx.accounts.registry.bump = *ctx.bumps.get("registry").unwrap();
+        Ok(())
+    }
+
+    pub fn initialize_leaderboard(ctx: Context<InitializeLeaderboard>) -> Result<()> {
+        ctx.accounts.leaderboard.bump = *ctx.bumps.get("leaderboard").unwrap();
+        Ok(())
+    }
+
+    // Only the program's upgrade authority can create the config; its admin takes over from
+    // there.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, params: GameConfigParams) -> Result<()> {
+        validate_config(&params)?;
+        let config = &mut ctx.accounts.config;
+        config.bump = *ctx.bumps.get("config").unwrap();
+        config.apply(params);
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, params: GameConfigParams) -> Result<()> {
+        validate_config(&params)?;
+        ctx.accounts.config.apply(params);
         Ok(())
     }
 
+    // Grows a config written with a u8 base_hp, widening it in place, and sets the default
+    // cooldown if the config also predates attack_cooldown_secs. Current configs are left
+    // alone.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let info = ctx.accounts.config.to_account_info();
+        {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() > 8 && data[..8] == GameConfig::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            let admin = Pubkey::deserialize(&mut &data[8..])
+                .map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+            require_keys_eq!(admin, ctx.accounts.admin.key(), ErrorCode::ConstraintHasOne);
+        }
+        let old_len = info.data_len();
+        if old_len >= GameConfig::SPACE {
+            return Ok(());
+        }
+
+        let rent = Rent::get()?.minimum_balance(GameConfig::SPACE);
+        let balance = info.lamports();
+        if balance < rent {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.admin.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                rent - balance,
+            )?;
+        }
+        info.realloc(GameConfig::SPACE, true)?;
+        widen_u8_field(&mut info.try_borrow_mut_data()?, GameConfig::BASE_HP_OFFSET, old_len);
+        let mut config = GameConfig::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        if old_len + 1 < GameConfig::SPACE {
+            config.attack_cooldown_secs = DEFAULT_ATTACK_COOLDOWN_SECS;
+        }
+        let mut data = info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data[..];
+        config.try_serialize(&mut writer)
+    }
+
+    // The game admin or the player's controller may set the level.
     pub fn update_level(ctx: Context<UpdateLevel>, new_level: u8) -> Result<()> {
-        ctx.accounts.player.level = new_level;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == ctx.accounts.config.admin
+                || ctx.accounts.player.controller == Some(authority),
+            CustomError::NotLevelAuthority
+        );
+        // Registration starts at level 1, so 0 is never a valid level.
+        require!(new_level >= 1, CustomError::InvalidLevel);
+        require!(new_level <= ctx.accounts.config.max_level, CustomError::LevelTooHigh);
+        let player = &mut ctx.accounts.player;
+        let old_level = player.level;
+        require!(new_level != old_level, CustomError::LevelUnchanged);
+        player.level = new_level;
+        emit!(LevelChanged {
+            player: player.key(),
+            old_level,
+            new_level,
+        });
+        ctx.accounts.leaderboard.record(player.key(), new_level);
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Level changes record themselves; this covers players who levelled before the board
+    // was initialized.
+    pub fn submit_score(ctx: Context<SubmitScore>) -> Result<()> {
+        let player = &ctx.accounts.player;
+        ctx.accounts.leaderboard.record(player.key(), player.level);
+        Ok(())
+    }
+
+    // Granting an achievement the player already has is a no-op, or fails with AlreadyUnlocked
+    // when `strict` is set.
+    pub fn grant_achievement(
+        ctx: Context<GrantAchievement>,
+        achievement_id: u8,
+        strict: bool,
+    ) -> Result<()> {
+        require!(achievement_id < MAX_ACHIEVEMENTS, CustomError::AchievementOutOfRange);
+        let player = &mut ctx.accounts.player;
+        if has_achievement(player.achievements, achievement_id) {
+            require!(!strict, CustomError::AlreadyUnlocked);
+            return Ok(());
+        }
+        player.achievements |= 1u64 << achievement_id;
+        emit!(AchievementGranted {
+            player: player.key(),
+            achievement_id,
+        });
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Both are idempotent, so moderation tooling can retry without checking first.
+    pub fn freeze_player(ctx: Context<SetPlayerFrozen>) -> Result<()> {
+        ctx.accounts.player.frozen = true;
+        Ok(())
+    }
+
+    pub fn unfreeze_player(ctx: Context<SetPlayerFrozen>) -> Result<()> {
+        ctx.accounts.player.frozen = false;
+        Ok(())
+    }
+
+    // Realloc refunds the rent difference to the owner when the name shrinks.
+    pub fn update_player_name(ctx: Context<UpdateName>, new_name: String) -> Result<()> {
+        validate_name(&new_name)?;
+        let now = Clock::get()?.unix_timestamp;
+        let player = &mut ctx.accounts.player;
+        // last_rename_ts starts at 0, so the first rename is always allowed.
+        require!(
+            now.saturating_sub(player.last_rename_ts) >= RENAME_COOLDOWN_SECS,
+            CustomError::RenameOnCooldown
+        );
+        player.name = new_name;
+        player.last_rename_ts = now;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn set_profile_uri(ctx: Context<SetProfileUri>, uri: String) -> Result<()> {
+        validate_uri(&uri)?;
+        ctx.accounts.player.metadata_uri = uri;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Admin-only; combat kills go through award_combat_experience instead.
+    pub fn add_experience(ctx: Context<AddExperience>, amount: u64) -> Result<()> {
+        grant_experience(
+            &mut ctx.accounts.player,
+            &ctx.accounts.config,
+            &mut ctx.accounts.leaderboard,
+            amount,
+        )?;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Called by behavior_module over CPI when its player lands a killing blow.
+    pub fn award_combat_experience(ctx: Context<AwardCombatExperience>, amount: u64) -> Result<()> {
+        grant_experience(
+            &mut ctx.accounts.player,
+            &ctx.accounts.config,
+            &mut ctx.accounts.leaderboard,
+            amount,
+        )?;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Called by behavior_module over CPI as its player enters or leaves a battle.
+    pub fn set_player_locked(ctx: Context<SetPlayerLocked>, locked: bool) -> Result<()> {
+        ctx.accounts.player.locked = locked;
+        Ok(())
+    }
+
+    pub fn initiate_ownership_transfer(
+        ctx: Context<TransferOwnership>,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        require_keys_neq!(new_owner, Pubkey::default(), CustomError::InvalidNewOwner);
+        require_keys_neq!(new_owner, ctx.accounts.owner.key(), CustomError::InvalidNewOwner);
+        ctx.accounts.player.pending_owner = Some(new_owner);
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn accept_ownership_transfer(ctx: Context<AcceptOwnershipTransfer>) -> Result<()> {
+        let player = &mut ctx.accounts.player;
+        player.owner = ctx.accounts.new_owner.key();
+        player.pending_owner = None;
+        player.controller = None;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn cancel_ownership_transfer(ctx: Context<TransferOwnership>) -> Result<()> {
+        let player = &mut ctx.accounts.player;
+        require!(player.pending_owner.is_some(), CustomError::NoPendingTransfer);
+        player.pending_owner = None;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Pass None to revoke. Ownership transfers also clear the controller.
+    pub fn set_controller(ctx: Context<SetController>, controller: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.player.controller = controller;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn transfer_gold(ctx: Context<TransferGold>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidGoldAmount);
+        let sender = &mut ctx.accounts.sender;
+        sender.gold = sender
+            .gold
+            .checked_sub(amount)
+            .ok_or(CustomError::InsufficientGold)?;
+        let recipient = &mut ctx.accounts.recipient;
+        recipient.gold = recipient
+            .gold
+            .checked_add(amount)
+            .ok_or(CustomError::GoldOverflow)?;
+        ctx.accounts.sender.touch()?;
+        Ok(())
+    }
+
+    // Admin-only source of gold; transfer_gold only moves what already exists.
+    pub fn grant_gold(ctx: Context<GrantGold>, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidGoldAmount);
+        let player = &mut ctx.accounts.player;
+        player.gold = player
+            .gold
+            .checked_add(amount)
+            .ok_or(CustomError::GoldOverflow)?;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn allocate_stat(ctx: Context<AllocateStat>, stat: StatKind, points: u8) -> Result<()> {
+        let player = &mut ctx.accounts.player;
+        player.unspent_points = player
+            .unspent_points
+            .checked_sub(points)
+            .ok_or(CustomError::NotEnoughStatPoints)?;
+        let attribute = match stat {
+            StatKind::Strength => &mut player.strength,
+            StatKind::Agility => &mut player.agility,
+            StatKind::Vitality => &mut player.vitality,
+        };
+        *attribute = attribute
+            .checked_add(points)
+            .ok_or(CustomError::StatOverflow)?;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn create_guild(ctx: Context<CreateGuild>, name: String, capacity: u16) -> Result<()> {
+        require!(!name.trim().is_empty(), CustomError::NameEmpty);
+        require!(name.len() <= MAX_GUILD_NAME_LEN, CustomError::GuildNameTooLong);
+        require!(
+            capacity > 0 && capacity <= MAX_GUILD_MEMBERS,
+            CustomError::InvalidGuildCapacity
+        );
+
+        let guild = &mut ctx.accounts.guild;
+        guild.name = name;
+        guild.leader = ctx.accounts.player.key();
+        guild.member_count = 1;
+        guild.capacity = capacity;
+        guild.bump = *ctx.bumps.get("guild").unwrap();
+
+        let membership = &mut ctx.accounts.membership;
+        membership.guild = guild.key();
+        membership.player = ctx.accounts.player.key();
+        membership.bump = *ctx.bumps.get("membership").unwrap();
+
+        ctx.accounts.player.guild = Some(guild.key());
+        ctx.accounts.player.touch()?;
         Ok(())
     }
+
+    pub fn join_guild(ctx: Context<JoinGuild>) -> Result<()> {
+        let guild = &mut ctx.accounts.guild;
+        require!(guild.member_count < guild.capacity, CustomError::GuildFull);
+        guild.member_count = guild
+            .member_count
+            .checked_add(1)
+            .ok_or(CustomError::GuildFull)?;
+
+        let membership = &mut ctx.accounts.membership;
+        membership.guild = guild.key();
+        membership.player = ctx.accounts.player.key();
+        membership.bump = *ctx.bumps.get("membership").unwrap();
+
+        ctx.accounts.player.guild = Some(guild.key());
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn leave_guild(ctx: Context<LeaveGuild>) -> Result<()> {
+        let guild = &mut ctx.accounts.guild;
+        require_keys_neq!(
+            guild.leader,
+            ctx.accounts.player.key(),
+            CustomError::LeaderCannotLeave
+        );
+        guild.member_count = guild
+            .member_count
+            .checked_sub(1)
+            .ok_or(CustomError::GuildMemberCountUnderflow)?;
+        ctx.accounts.player.guild = None;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn transfer_guild_leadership(ctx: Context<TransferGuildLeadership>) -> Result<()> {
+        ctx.accounts.guild.leader = ctx.accounts.new_leader_membership.player;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // A leader left on their own closes the guild instead of leaving it, which frees the name.
+    pub fn disband_guild(ctx: Context<DisbandGuild>) -> Result<()> {
+        ctx.accounts.player.guild = None;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn init_inventory(ctx: Context<InitInventory>) -> Result<()> {
+        let inventory = &mut ctx.accounts.inventory;
+        inventory.player = ctx.accounts.player.key();
+        inventory.bump = *ctx.bumps.get("inventory").unwrap();
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn add_item_to_inventory(ctx: Context<AddItemToInventory>) -> Result<()> {
+        let inventory = &mut ctx.accounts.inventory;
+        let mint = ctx.accounts.mint.key();
+        require!(!inventory.items.contains(&mint), CustomError::ItemAlreadyInInventory);
+        require!(inventory.items.len() < MAX_INVENTORY, CustomError::InventoryFull);
+        inventory.items.push(mint);
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn remove_item_from_inventory(
+        ctx: Context<RemoveItemFromInventory>,
+        mint: Pubkey,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.player.equipment.contains(&mint),
+            CustomError::ItemEquipped
+        );
+        let inventory = &mut ctx.accounts.inventory;
+        let index = inventory
+            .items
+            .iter()
+            .position(|item| *item == mint)
+            .ok_or(CustomError::ItemNotFound)?;
+        inventory.items.remove(index);
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn equip_item(ctx: Context<EquipItem>, slot: SlotKind, mint: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.inventory.items.contains(&mint),
+            CustomError::ItemNotFound
+        );
+        let equipped = ctx.accounts.player.equipment.slot_mut(slot);
+        require!(equipped.is_none(), CustomError::SlotOccupied);
+        *equipped = Some(mint);
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    pub fn unequip_item(ctx: Context<UnequipItem>, slot: SlotKind) -> Result<()> {
+        let equipped = ctx.accounts.player.equipment.slot_mut(slot);
+        require!(equipped.is_some(), CustomError::SlotEmpty);
+        *equipped = None;
+        ctx.accounts.player.touch()?;
+        Ok(())
+    }
+
+    // Returns the version the account is at afterwards; already-current accounts are left as is.
+    pub fn migrate_player(ctx: Context<MigratePlayer>) -> Result<u8> {
+        let info = ctx.accounts.player.to_account_info();
+        let (version, owner) = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() > 8 && data[..8] == PlayerState::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            let owner = Pubkey::deserialize(&mut &data[9..])
+                .map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+            (data[8], owner)
+        };
+        require_keys_eq!(owner, ctx.accounts.owner.key(), ErrorCode::ConstraintHasOne);
+        require!(version <= PLAYER_VERSION, CustomError::UnknownVersion);
+        if version == PLAYER_VERSION {
+            return Ok(PLAYER_VERSION);
+        }
+
+        // Grow to the largest layout so the zero-filled tail deserializes, then shrink to
+        // the exact size once the variable-length fields are known.
+        let old_len = info.data_len();
+        info.realloc(PlayerState::SPACE.max(old_len), true)?;
+        if version < NO_HP_VERSION {
+            let mut data = info.try_borrow_mut_data()?;
+            let name_len = u32::deserialize(&mut &data[41..])
+                .map_err(|_| ErrorCode::AccountDidNotDeserialize)? as usize;
+            require!(name_len <= MAX_NAME_LEN, ErrorCode::AccountDidNotDeserialize);
+            // discriminator, version, owner, name, level, bump, experience; hp and max_hp were
+            // a byte each.
+            let hp_offset = 8 + 1 + 32 + 4 + name_len + 1 + 1 + 8;
+            remove_bytes(&mut data, hp_offset, 2, old_len);
+        }
+        let mut player = PlayerState::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        fill_migration_defaults(&mut player, version, &mut ctx.accounts.registry)?;
+        player.version = PLAYER_VERSION;
+        player.touch()?;
+        let new_len = PlayerState::space(&player.name, &player.metadata_uri);
+        info.realloc(new_len, false)?;
+        {
+            let mut data = info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data[..];
+            player.try_serialize(&mut writer)?;
+        }
+
+        let rent = Rent::get()?.minimum_balance(new_len);
+        let balance = info.lamports();
+        if balance < rent {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.owner.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                rent - balance,
+            )?;
+        } else if balance > rent {
+            let owner_info = ctx.accounts.owner.to_account_info();
+            **info.try_borrow_mut_lamports()? -= balance - rent;
+            **owner_info.try_borrow_mut_lamports()? += balance - rent;
+        }
+        Ok(PLAYER_VERSION)
+    }
+
+    // The inventory PDA may not exist; one that does must be empty and is closed too, so
+    // closing the player never strands items or rent.
+    pub fn close_player(ctx: Context<ClosePlayer>) -> Result<()> {
+        let inventory_info = ctx.accounts.inventory.to_account_info();
+        if inventory_info.owner == &crate::ID {
+            let inventory = Account::<Inventory>::try_from(&inventory_info)?;
+            require!(inventory.items.is_empty(), CustomError::InventoryNotEmpty);
+            inventory.close(ctx.accounts.owner.to_account_info())?;
+        }
+        emit!(PlayerClosed {
+            player: ctx.accounts.player.key(),
+            owner: ctx.accounts.owner.key(),
+        });
+        Ok(())
+    }
+}
+
+pub fn has_achievement(mask: u64, achievement_id: u8) -> bool {
+    achievement_id < MAX_ACHIEVEMENTS && mask & (1u64 << achievement_id) != 0
+}
+
+// Total experience needed to advance past `level`.
+pub fn xp_required(level: u8) -> u64 {
+    (level as u64).pow(2) * 100
+}
+
+// Stops at max_level (never above MAX_LEVEL) so a huge grant can't spin the loop past
+// the compute budget.
+fn apply_level_ups(player: &mut PlayerState, max_level: u8) -> u8 {
+    let old_level = player.level;
+    while player.level < max_level && player.experience >= xp_required(player.level) {
+        player.level += 1;
+    }
+    player.level.saturating_sub(old_level)
+}
+
+fn grant_experience(
+    player: &mut Account<PlayerState>,
+    config: &GameConfig,
+    leaderboard: &mut Leaderboard,
+    amount: u64,
+) -> Result<()> {
+    let player_key = player.key();
+    let old_level = player.level;
+    let scaled = amount
+        .checked_mul(config.xp_multiplier as u64)
+        .ok_or(CustomError::ExperienceOverflow)?
+        / 100;
+    player.experience = player
+        .experience
+        .checked_add(scaled)
+        .ok_or(CustomError::ExperienceOverflow)?;
+    let levels_gained = apply_level_ups(player, config.max_level);
+    if levels_gained > 0 {
+        emit!(LeveledUp {
+            player: player_key,
+            old_level,
+            new_level: player.level,
+            levels_gained,
+        });
+        leaderboard.record(player_key, player.level);
+    }
+    Ok(())
+}
+
+fn new_player(
+    owner: Pubkey,
+    name: String,
+    bump: u8,
+    registry: &mut Registry,
+) -> Result<PlayerState> {
+    let now = Clock::get()?.unix_timestamp;
+    Ok(PlayerState {
+        version: PLAYER_VERSION,
+        owner,
+        name,
+        level: 1,
+        bump,
+        experience: 0,
+        attack_power: STARTING_ATTACK_POWER,
+        defense: STARTING_DEFENSE,
+        mana: STARTING_MANA,
+        locked: false,
+        registered_by: owner,
+        guild: None,
+        strength: STARTING_ATTRIBUTE,
+        agility: STARTING_ATTRIBUTE,
+        vitality: STARTING_ATTRIBUTE,
+        unspent_points: STARTING_STAT_POINTS,
+        pending_owner: None,
+        player_index: registry.next_index()?,
+        created_at: now,
+        last_active: now,
+        achievements: 0,
+        metadata_uri: String::new(),
+        frozen: false,
+        equipment: EquipmentSlots::default(),
+        last_rename_ts: 0,
+        gold: 0,
+        controller: None,
+    })
+}
+
+// Fields added after `from_version` come back zeroed from the realloc; set any
+// that need a non-zero default here.
+fn fill_migration_defaults(
+    player: &mut PlayerState,
+    from_version: u8,
+    registry: &mut Registry,
+) -> Result<()> {
+    if from_version < 3 {
+        player.player_index = registry.next_index()?;
+    }
+    Ok(())
+}
+
+// Mirrors Anchor's `init`: create_account fails on an address that already holds lamports,
+// which anyone can send to a PDA to block it, so those are topped up, allocated and assigned.
+fn create_player_account<'info>(
+    system_program: &Program<'info, System>,
+    payer: &Signer<'info>,
+    account: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    space: usize,
+    rent: &Rent,
+) -> Result<()> {
+    let system = system_program.to_account_info();
+    let lamports = rent.minimum_balance(space);
+    let balance = account.lamports();
+    if balance == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system,
+                system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+                &[seeds],
+            ),
+            lamports,
+            space as u64,
+            &crate::ID,
+        );
+    }
+    if balance < lamports {
+        system_program::transfer(
+            CpiContext::new(
+                system.clone(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            lamports - balance,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[seeds],
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system,
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[seeds],
+        ),
+        &crate::ID,
+    )
+}
+
+// Turns the u8 at `offset` into a little-endian u16 by shifting the rest of the first `len`
+// bytes up by one. `data` needs a spare byte past `len`.
+fn widen_u8_field(data: &mut [u8], offset: usize, len: usize) {
+    data.copy_within(offset + 1..len, offset + 2);
+    data[offset + 1] = 0;
+}
+
+// Drops `count` bytes at `offset` by shifting the rest of the first `len` bytes down and
+// zeroing the bytes that frees at the end.
+fn remove_bytes(data: &mut [u8], offset: usize, count: usize, len: usize) {
+    data.copy_within(offset + count..len, offset);
+    data[len - count..len].fill(0);
+}
+
+// An empty uri clears the profile.
+fn validate_uri(uri: &str) -> Result<()> {
+    if uri.is_empty() {
+        return Ok(());
+    }
+    require!(uri.len() <= MAX_URI_LEN, CustomError::UriTooLong);
+    require!(!uri.contains('\0'), CustomError::UriContainsNull);
+    require!(
+        uri.starts_with("https://") || uri.starts_with("ipfs://"),
+        CustomError::InvalidUriScheme
+    );
+    Ok(())
+}
+
+fn validate_config(params: &GameConfigParams) -> Result<()> {
+    require_keys_neq!(params.admin, Pubkey::default(), CustomError::InvalidConfig);
+    require!(
+        params.max_level >= 1 && params.max_level <= MAX_LEVEL,
+        CustomError::InvalidConfig
+    );
+    require!(params.base_hp > 0, CustomError::InvalidConfig);
+    require!(params.xp_multiplier > 0, CustomError::InvalidConfig);
+    require!(
+        params.attack_cooldown_secs.map_or(true, |secs| secs >= 0),
+        CustomError::InvalidConfig
+    );
+    Ok(())
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    require!(!name.trim().is_empty(), CustomError::NameEmpty);
+    require!(name.len() <= MAX_NAME_LEN, CustomError::NameTooLong);
+    Ok(())
 }
 
 #[derive(Accounts)]
+#[instruction(name: String)]
 pub struct RegisterPlayer<'info> {
-    #[account(init, payer = authority, space = 8 + 32 + 32 + 1)]
+    #[account(
+        init,
+        payer = authority,
+        space = PlayerState::space(&name, ""),
+        seeds = [PLAYER_SEED, authority.key().as_ref()],
+        bump
+    )]
     pub player: Account<'info, PlayerState>,
+    // Players can only register into an initialized game.
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut, seeds = [REGISTRY_SEED], bump = registry.bump)]
+    pub registry: Account<'info, Registry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterPlayersBatch<'info> {
+    // Players can only register into an initialized game.
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut, seeds = [REGISTRY_SEED], bump = registry.bump)]
+    pub registry: Account<'info, Registry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRegistry<'info> {
+    #[account(init, payer = payer, space = Registry::SPACE, seeds = [REGISTRY_SEED], bump)]
+    pub registry: Account<'info, Registry>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLeaderboard<'info> {
+    #[account(init, payer = payer, space = Leaderboard::SPACE, seeds = [LEADERBOARD_SEED], bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(init, payer = authority, space = GameConfig::SPACE, seeds = [CONFIG_SEED], bump)]
+    pub config: Account<'info, GameConfig>,
     #[account(mut)]
     pub authority: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, StateModule>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ CustomError::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: older layouts don't deserialize as GameConfig, so migrate_config checks the
+    /// discriminator and admin by hand.
+    #[account(mut, owner = crate::ID, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateLevel<'info> {
-    #[account(mut, has_one = owner)]
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut, seeds = [LEADERBOARD_SEED], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    // The admin or the player's controller; checked in update_level.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GrantAchievement<'info> {
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
     pub player: Account<'info, PlayerState>,
+    #[account(has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPlayerFrozen<'info> {
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_name: String)]
+pub struct UpdateName<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump,
+        realloc = PlayerState::space(&new_name, &player.metadata_uri),
+        realloc::payer = owner,
+        realloc::zero = false
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut)]
     pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(uri: String)]
+pub struct SetProfileUri<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump,
+        realloc = PlayerState::space(&player.name, &uri),
+        realloc::payer = owner,
+        realloc::zero = false
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddExperience<'info> {
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut, seeds = [LEADERBOARD_SEED], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitScore<'info> {
+    #[account(
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut, seeds = [LEADERBOARD_SEED], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AwardCombatExperience<'info> {
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    #[account(mut, seeds = [LEADERBOARD_SEED], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    // Only behavior_module can sign for this PDA.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump, seeds::program = BEHAVIOR_PROGRAM_ID)]
+    pub combat_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPlayerLocked<'info> {
+    // Not gated on frozen, so a frozen player's battle can still end.
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    // Only behavior_module can sign for this PDA.
+    #[account(seeds = [COMBAT_AUTHORITY_SEED], bump, seeds::program = BEHAVIOR_PROGRAM_ID)]
+    pub combat_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferOwnership<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnershipTransfer<'info> {
+    #[account(
+        mut,
+        constraint = player.pending_owner == Some(new_owner.key()) @ CustomError::NotPendingOwner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    pub new_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePlayer<'info> {
+    /// CHECK: older layouts don't deserialize as PlayerState, so migrate_player checks the
+    /// discriminator and owner by hand.
+    #[account(mut, owner = crate::ID)]
+    pub player: UncheckedAccount<'info>,
+    #[account(mut, seeds = [REGISTRY_SEED], bump = registry.bump)]
+    pub registry: Account<'info, Registry>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePlayer<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        close = owner,
+        constraint = !player.locked @ CustomError::PlayerLocked,
+        constraint = player.guild.is_none() @ CustomError::StillInGuild,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    /// CHECK: the player's inventory PDA, which may not have been created; close_player
+    /// checks it by hand.
+    #[account(mut, seeds = [INVENTORY_SEED, player.key().as_ref()], bump)]
+    pub inventory: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AllocateStat<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct CreateGuild<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.guild.is_none() @ CustomError::AlreadyInGuild,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        init,
+        payer = owner,
+        space = Guild::SPACE,
+        seeds = [GUILD_SEED, name.as_bytes()],
+        bump
+    )]
+    pub guild: Account<'info, Guild>,
+    #[account(
+        init,
+        payer = owner,
+        space = GuildMembership::SPACE,
+        seeds = [MEMBER_SEED, guild.key().as_ref(), player.key().as_ref()],
+        bump
+    )]
+    pub membership: Account<'info, GuildMembership>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinGuild<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.guild.is_none() @ CustomError::AlreadyInGuild,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut)]
+    pub guild: Account<'info, Guild>,
+    #[account(
+        init,
+        payer = owner,
+        space = GuildMembership::SPACE,
+        seeds = [MEMBER_SEED, guild.key().as_ref(), player.key().as_ref()],
+        bump
+    )]
+    pub membership: Account<'info, GuildMembership>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveGuild<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut)]
+    pub guild: Account<'info, Guild>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = guild,
+        has_one = player,
+        seeds = [MEMBER_SEED, guild.key().as_ref(), player.key().as_ref()],
+        bump = membership.bump
+    )]
+    pub membership: Account<'info, GuildMembership>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferGuildLeadership<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(mut, constraint = guild.leader == player.key() @ CustomError::NotGuildLeader)]
+    pub guild: Account<'info, Guild>,
+    #[account(
+        has_one = guild,
+        seeds = [MEMBER_SEED, guild.key().as_ref(), new_leader_membership.player.as_ref()],
+        bump = new_leader_membership.bump
+    )]
+    pub new_leader_membership: Account<'info, GuildMembership>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DisbandGuild<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        close = owner,
+        constraint = guild.leader == player.key() @ CustomError::NotGuildLeader,
+        constraint = guild.member_count == 1 @ CustomError::GuildNotEmpty,
+        seeds = [GUILD_SEED, guild.name.as_bytes()],
+        bump = guild.bump
+    )]
+    pub guild: Account<'info, Guild>,
+    #[account(
+        mut,
+        close = owner,
+        has_one = guild,
+        has_one = player,
+        seeds = [MEMBER_SEED, guild.key().as_ref(), player.key().as_ref()],
+        bump = membership.bump
+    )]
+    pub membership: Account<'info, GuildMembership>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitInventory<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        init,
+        payer = owner,
+        space = Inventory::SPACE,
+        seeds = [INVENTORY_SEED, player.key().as_ref()],
+        bump
+    )]
+    pub inventory: Account<'info, Inventory>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddItemToInventory<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        has_one = player,
+        seeds = [INVENTORY_SEED, player.key().as_ref()],
+        bump = inventory.bump
+    )]
+    pub inventory: Account<'info, Inventory>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        constraint = token_account.owner == owner.key() @ CustomError::ItemNotHeld,
+        constraint = token_account.mint == mint.key() @ CustomError::ItemNotHeld,
+        constraint = token_account.amount >= 1 @ CustomError::ItemNotHeld
+    )]
+    pub token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveItemFromInventory<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        has_one = player,
+        seeds = [INVENTORY_SEED, player.key().as_ref()],
+        bump = inventory.bump
+    )]
+    pub inventory: Account<'info, Inventory>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetController<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferGold<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = sender.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !sender.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, sender.registered_by.as_ref()],
+        bump = sender.bump
+    )]
+    pub sender: Account<'info, PlayerState>,
+    #[account(
+        mut,
+        constraint = recipient.key() != sender.key() @ CustomError::SelfTransfer,
+        constraint = recipient.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        seeds = [PLAYER_SEED, recipient.registered_by.as_ref()],
+        bump = recipient.bump
+    )]
+    pub recipient: Account<'info, PlayerState>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GrantGold<'info> {
+    #[account(
+        mut,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, GameConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EquipItem<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    #[account(
+        has_one = player,
+        seeds = [INVENTORY_SEED, player.key().as_ref()],
+        bump = inventory.bump
+    )]
+    pub inventory: Account<'info, Inventory>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnequipItem<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = player.version == PLAYER_VERSION @ PlayerError::MigrationRequired,
+        constraint = !player.frozen @ PlayerError::PlayerFrozen,
+        seeds = [PLAYER_SEED, player.registered_by.as_ref()],
+        bump = player.bump
+    )]
+    pub player: Account<'info, PlayerState>,
+    pub owner: Signer<'info>,
+}
+
+#[account]
+pub struct Registry {
+    pub total_players: u64,
+    pub bump: u8,
+}
+
+impl Registry {
+    pub const SPACE: usize = 8 + 8 + 1;
+
+    // Hands out the next stable player index.
+    fn next_index(&mut self) -> Result<u64> {
+        let index = self.total_players;
+        self.total_players = index
+            .checked_add(1)
+            .ok_or(CustomError::RegistryOverflow)?;
+        Ok(index)
+    }
+}
+
+#[account]
+pub struct Leaderboard {
+    // Sorted by level, highest first.
+    pub entries: Vec<LeaderboardEntry>,
+    pub bump: u8,
+}
+
+impl Leaderboard {
+    pub const SPACE: usize = 8 + 4 + LeaderboardEntry::SIZE * LEADERBOARD_SIZE + 1;
+
+    // Updates the player's entry if present, otherwise inserts it when there is room or
+    // it beats the current last place.
+    fn record(&mut self, player: Pubkey, level: u8) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.player == player) {
+            entry.level = level;
+        } else if self.entries.len() < LEADERBOARD_SIZE {
+            self.entries.push(LeaderboardEntry { player, level });
+        } else if self.entries.last().map_or(false, |last| level > last.level) {
+            *self.entries.last_mut().unwrap() = LeaderboardEntry { player, level };
+        } else {
+            return;
+        }
+        self.entries.sort_by_key(|entry| std::cmp::Reverse(entry.level));
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderboardEntry {
+    pub player: Pubkey,
+    pub level: u8,
+}
+
+impl LeaderboardEntry {
+    pub const SIZE: usize = 32 + 1;
+}
+
+#[account]
+pub struct GameConfig {
+    pub admin: Pubkey,
+    pub max_level: u8,
+    // Max hp behavior_module gives a player entering combat.
+    pub base_hp: u16,
+    // Percentage applied to experience grants; 100 leaves them unchanged.
+    pub xp_multiplier: u16,
+    pub bump: u8,
+    // Minimum seconds between two attacks by the same player; read by behavior_module.
+    pub attack_cooldown_secs: i64,
+}
+
+impl GameConfig {
+    pub const SPACE: usize = 8 + 32 + 1 + 2 + 2 + 1 + 8;
+    // base_hp was a u8 in configs written before migrate_config widened it.
+    const BASE_HP_OFFSET: usize = 8 + 32 + 1;
+
+    fn apply(&mut self, params: GameConfigParams) {
+        self.admin = params.admin;
+        self.max_level = params.max_level;
+        self.base_hp = params.base_hp;
+        self.xp_multiplier = params.xp_multiplier;
+        self.attack_cooldown_secs = params
+            .attack_cooldown_secs
+            .unwrap_or(DEFAULT_ATTACK_COOLDOWN_SECS);
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct GameConfigParams {
+    pub admin: Pubkey,
+    pub max_level: u8,
+    pub base_hp: u16,
+    pub xp_multiplier: u16,
+    pub attack_cooldown_secs: Option<i64>,
 }
 
 #[account]
-pub struct PlayerState {
+pub struct Inventory {
+    pub player: Pubkey,
+    pub items: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl Inventory {
+    pub const SPACE: usize = 8 + 32 + 4 + 32 * MAX_INVENTORY + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum StatKind {
+    Strength,
+    Agility,
+    Vitality,
+}
+
+#[account]
+pub struct Guild {
+    pub name: String,
+    // Player account of the leader, not the owning wallet.
+    pub leader: Pubkey,
+    pub member_count: u16,
+    pub capacity: u16,
+    pub bump: u8,
+}
+
+impl Guild {
+    pub const SPACE: usize = 8 + 4 + MAX_GUILD_NAME_LEN + 32 + 2 + 2 + 1;
+}
+
+#[account]
+pub struct GuildMembership {
+    pub guild: Pubkey,
+    pub player: Pubkey,
+    pub bump: u8,
+}
+
+impl GuildMembership {
+    pub const SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+#[event]
+pub struct PlayerRegistered {
+    pub player: Pubkey,
     pub owner: Pubkey,
     pub name: String,
-    pub level: u8,
+}
+
+#[event]
+pub struct LevelChanged {
+    pub player: Pubkey,
+    pub old_level: u8,
+    pub new_level: u8,
+}
+
+#[event]
+pub struct PlayerClosed {
+    pub player: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct AchievementGranted {
+    pub player: Pubkey,
+    pub achievement_id: u8,
+}
+
+#[event]
+pub struct LeveledUp {
+    pub player: Pubkey,
+    pub old_level: u8,
+    pub new_level: u8,
+    pub levels_gained: u8,
+}
+
+#[error_code]
+pub enum CustomError {
+    #[msg("Player name cannot be empty.")]
+    NameEmpty,
+    #[msg("Player name is too long.")]
+    NameTooLong,
+    #[msg("Experience would overflow.")]
+    ExperienceOverflow,
+    #[msg("Player is locked and cannot be closed.")]
+    PlayerLocked,
+    #[msg("New owner must be a valid public key.")]
+    InvalidNewOwner,
+    #[msg("Guild name is too long.")]
+    GuildNameTooLong,
+    #[msg("Guild capacity must be between one and MAX_GUILD_MEMBERS.")]
+    InvalidGuildCapacity,
+    #[msg("Guild is full.")]
+    GuildFull,
+    #[msg("Player is already in a guild.")]
+    AlreadyInGuild,
+    #[msg("Player must leave their guild first.")]
+    StillInGuild,
+    #[msg("Guild leader must transfer leadership or disband the guild before leaving.")]
+    LeaderCannotLeave,
+    #[msg("Only the guild leader can do this.")]
+    NotGuildLeader,
+    #[msg("Not enough unspent stat points.")]
+    NotEnoughStatPoints,
+    #[msg("Stat value would overflow.")]
+    StatOverflow,
+    #[msg("Inventory is full.")]
+    InventoryFull,
+    #[msg("Item is not in the inventory.")]
+    ItemNotFound,
+    #[msg("Caller does not hold this item.")]
+    ItemNotHeld,
+    #[msg("Player account version is newer than this program.")]
+    UnknownVersion,
+    #[msg("Level exceeds the maximum level.")]
+    LevelTooHigh,
+    #[msg("Level must be at least 1.")]
+    InvalidLevel,
+    #[msg("Player is already at this level.")]
+    LevelUnchanged,
+    #[msg("No ownership transfer is pending.")]
+    NoPendingTransfer,
+    #[msg("Signer is not the pending owner.")]
+    NotPendingOwner,
+    #[msg("Invalid game config.")]
+    InvalidConfig,
+    #[msg("Player registry count would overflow.")]
+    RegistryOverflow,
+    #[msg("Achievement id is out of range.")]
+    AchievementOutOfRange,
+    #[msg("Profile uri is too long.")]
+    UriTooLong,
+    #[msg("Profile uri must start with https:// or ipfs://.")]
+    InvalidUriScheme,
+    #[msg("Profile uri cannot contain null bytes.")]
+    UriContainsNull,
+    #[msg("Too many players in one batch.")]
+    BatchTooLarge,
+    #[msg("Expected an owner and player account for every name.")]
+    BatchAccountsMismatch,
+    #[msg("Player account does not match the expected address.")]
+    InvalidPlayerAccount,
+    #[msg("Item is already in the inventory.")]
+    ItemAlreadyInInventory,
+    #[msg("Slot already holds an item; unequip it first.")]
+    SlotOccupied,
+    #[msg("Slot is empty.")]
+    SlotEmpty,
+    #[msg("Unequip the item before removing it from the inventory.")]
+    ItemEquipped,
+    #[msg("Player was renamed too recently.")]
+    RenameOnCooldown,
+    #[msg("Gold amount must be greater than zero.")]
+    InvalidGoldAmount,
+    #[msg("Not enough gold.")]
+    InsufficientGold,
+    #[msg("Recipient gold balance would overflow.")]
+    GoldOverflow,
+    #[msg("Cannot transfer gold to yourself.")]
+    SelfTransfer,
+    #[msg("Achievement is already unlocked.")]
+    AlreadyUnlocked,
+    #[msg("Only the game admin or the player's controller can set its level.")]
+    NotLevelAuthority,
+    #[msg("Only the program's upgrade authority can create the config.")]
+    NotUpgradeAuthority,
+    #[msg("Every owner in a batch must sign.")]
+    OwnerMustSign,
+    #[msg("Player account already exists.")]
+    PlayerAlreadyExists,
+    #[msg("Only a guild with no members besides its leader can be disbanded.")]
+    GuildNotEmpty,
+    #[msg("Guild member count would underflow.")]
+    GuildMemberCountUnderflow,
+    #[msg("Remove every item from the inventory before closing the player.")]
+    InventoryNotEmpty,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player_with_experience(experience: u64) -> PlayerState {
+        PlayerState {
+            level: 1,
+            experience,
+            ..PlayerState::default()
+        }
+    }
+
+    #[test]
+    fn xp_required_grows_with_level() {
+        assert_eq!(xp_required(1), 100);
+        assert_eq!(xp_required(2), 400);
+        for level in 1..u8::MAX {
+            assert!(xp_required(level + 1) > xp_required(level));
+        }
+    }
+
+    #[test]
+    fn apply_level_ups_crosses_every_threshold_reached() {
+        let mut player = player_with_experience(xp_required(2) - 1);
+        assert_eq!(apply_level_ups(&mut player, MAX_LEVEL), 1);
+        assert_eq!(player.level, 2);
+
+        player.experience = xp_required(2);
+        assert_eq!(apply_level_ups(&mut player, MAX_LEVEL), 1);
+        assert_eq!(player.level, 3);
+    }
+
+    #[test]
+    fn apply_level_ups_stops_at_max_level() {
+        let mut player = player_with_experience(u64::MAX);
+        assert_eq!(apply_level_ups(&mut player, 20), 19);
+        assert_eq!(player.level, 20);
+        assert_eq!(apply_level_ups(&mut player, 20), 0);
+    }
+
+    fn empty_leaderboard() -> Leaderboard {
+        Leaderboard {
+            entries: Vec::new(),
+            bump: 0,
+        }
+    }
+
+    fn levels(board: &Leaderboard) -> Vec<u8> {
+        board.entries.iter().map(|entry| entry.level).collect()
+    }
+
+    #[test]
+    fn leaderboard_record_inserts_into_an_empty_board() {
+        let mut board = empty_leaderboard();
+        let player = Pubkey::new_unique();
+        board.record(player, 3);
+        assert_eq!(board.entries.len(), 1);
+        assert_eq!(board.entries[0].player, player);
+        assert_eq!(board.entries[0].level, 3);
+    }
+
+    #[test]
+    fn leaderboard_record_displaces_the_last_place_when_full() {
+        let mut board = empty_leaderboard();
+        for level in 1..=LEADERBOARD_SIZE as u8 {
+            board.record(Pubkey::new_unique(), level);
+        }
+        let last = board.entries.last().unwrap().player;
+        let newcomer = Pubkey::new_unique();
+        board.record(newcomer, 2);
+        assert_eq!(board.entries.len(), LEADERBOARD_SIZE);
+        assert!(board.entries.iter().all(|entry| entry.player != last));
+        assert!(board.entries.iter().any(|entry| entry.player == newcomer));
+    }
+
+    #[test]
+    fn leaderboard_record_updates_an_existing_entry_in_place() {
+        let mut board = empty_leaderboard();
+        let player = Pubkey::new_unique();
+        board.record(player, 2);
+        board.record(Pubkey::new_unique(), 5);
+        board.record(player, 7);
+        assert_eq!(board.entries.len(), 2);
+        assert_eq!(board.entries[0].player, player);
+        assert_eq!(levels(&board), vec![7, 5]);
+    }
+
+    #[test]
+    fn leaderboard_record_keeps_entries_sorted_highest_first() {
+        let mut board = empty_leaderboard();
+        for level in [4, 9, 1, 6] {
+            board.record(Pubkey::new_unique(), level);
+        }
+        assert_eq!(levels(&board), vec![9, 6, 4, 1]);
+    }
+
+    #[test]
+    fn leaderboard_record_ignores_a_level_too_low_for_a_full_board() {
+        let mut board = empty_leaderboard();
+        for _ in 0..LEADERBOARD_SIZE {
+            board.record(Pubkey::new_unique(), 5);
+        }
+        let before = levels(&board);
+        let latecomer = Pubkey::new_unique();
+        // Ties don't displace the last place either.
+        board.record(latecomer, 5);
+        board.record(latecomer, 4);
+        assert_eq!(levels(&board), before);
+        assert!(board.entries.iter().all(|entry| entry.player != latecomer));
+    }
+
+    #[test]
+    fn leaderboard_record_never_grows_past_leaderboard_size() {
+        let mut board = empty_leaderboard();
+        for level in 1..=u8::MAX {
+            board.record(Pubkey::new_unique(), level);
+        }
+        assert_eq!(board.entries.len(), LEADERBOARD_SIZE);
+        let expected: Vec<u8> = (0..LEADERBOARD_SIZE as u8).map(|rank| u8::MAX - rank).collect();
+        assert_eq!(levels(&board), expected);
+    }
 }
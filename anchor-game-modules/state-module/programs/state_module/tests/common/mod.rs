@@ -0,0 +1,20 @@
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::pubkey::Pubkey;
+
+// Runs the program natively under `cargo test`; `cargo test-sbf` loads the built .so instead.
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("state_module", state_module::ID, processor!(process_instruction))
+}
+
+// anchor's entry wants the account slice to live as long as the accounts, which processor! can't
+// express, so hand it a leaked copy.
+fn process_instruction<'info>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo<'info>],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    state_module::entry(program_id, accounts, data)
+}
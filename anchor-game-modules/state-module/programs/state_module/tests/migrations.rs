@@ -0,0 +1,257 @@
+mod common;
+
+use anchor_lang::{
+    AccountDeserialize, AccountSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use solana_program_test::BanksClient;
+use solana_sdk::account::Account;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+use state_module::{
+    EquipmentSlots, GameConfig, PlayerState, Registry, CONFIG_SEED,
+    DEFAULT_ATTACK_COOLDOWN_SECS, PLAYER_SEED, PLAYER_VERSION, REGISTRY_SEED,
+};
+
+const SOL: u64 = 1_000_000_000;
+
+fn program_account(data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: state_module::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+// A config written before base_hp was widened, optionally before attack_cooldown_secs too.
+fn legacy_config(admin: &Pubkey, bump: u8, attack_cooldown_secs: Option<i64>) -> Vec<u8> {
+    let mut data = GameConfig::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(admin.as_ref());
+    data.push(20); // max_level
+    data.push(100); // base_hp
+    data.extend_from_slice(&150u16.to_le_bytes()); // xp_multiplier
+    data.push(bump);
+    if let Some(secs) = attack_cooldown_secs {
+        data.extend_from_slice(&secs.to_le_bytes());
+    }
+    data
+}
+
+// Starts a validator holding `config` and a funded `signer` and runs migrate_config.
+async fn migrate_config(config_data: Vec<u8>, signer: &Keypair) -> (BanksClient, bool) {
+    let (config, _) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let mut test = common::program_test();
+    test.add_account(config, program_account(config_data));
+    test.add_account(signer.pubkey(), Account::new(10 * SOL, 0, &system_program::ID));
+    let (mut banks, payer, blockhash) = test.start().await;
+
+    let migrate = Instruction {
+        program_id: state_module::ID,
+        accounts: state_module::accounts::MigrateConfig {
+            config,
+            admin: signer.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: state_module::instruction::MigrateConfig {}.data(),
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[migrate],
+        Some(&payer.pubkey()),
+        &[&payer, signer],
+        blockhash,
+    );
+    let ok = banks.process_transaction(tx).await.is_ok();
+    (banks, ok)
+}
+
+async fn fetch_config(banks: &mut BanksClient) -> GameConfig {
+    let (config, _) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let account = banks.get_account(config).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), GameConfig::SPACE);
+    assert!(account.lamports >= Rent::default().minimum_balance(GameConfig::SPACE));
+    GameConfig::try_deserialize(&mut &account.data[..]).unwrap()
+}
+
+#[tokio::test]
+async fn migrate_config_grows_a_config_without_a_cooldown() {
+    let admin = Keypair::new();
+    let (_, bump) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let (mut banks, ok) = migrate_config(legacy_config(&admin.pubkey(), bump, None), &admin).await;
+    assert!(ok);
+
+    let migrated = fetch_config(&mut banks).await;
+    assert_eq!(migrated.admin, admin.pubkey());
+    assert_eq!(migrated.max_level, 20);
+    assert_eq!(migrated.base_hp, 100);
+    assert_eq!(migrated.xp_multiplier, 150);
+    assert_eq!(migrated.bump, bump);
+    assert_eq!(migrated.attack_cooldown_secs, DEFAULT_ATTACK_COOLDOWN_SECS);
+}
+
+#[tokio::test]
+async fn migrate_config_widens_base_hp_and_keeps_the_cooldown() {
+    let admin = Keypair::new();
+    let (_, bump) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let data = legacy_config(&admin.pubkey(), bump, Some(3));
+    let (mut banks, ok) = migrate_config(data, &admin).await;
+    assert!(ok);
+
+    let migrated = fetch_config(&mut banks).await;
+    assert_eq!(migrated.base_hp, 100);
+    assert_eq!(migrated.xp_multiplier, 150);
+    assert_eq!(migrated.bump, bump);
+    assert_eq!(migrated.attack_cooldown_secs, 3);
+}
+
+#[tokio::test]
+async fn migrate_config_requires_the_admin() {
+    let admin = Keypair::new();
+    let (_, bump) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let data = legacy_config(&admin.pubkey(), bump, None);
+    let (_, ok) = migrate_config(data, &Keypair::new()).await;
+    assert!(!ok);
+}
+
+// Starts a validator holding `owner`'s player account as `data` and runs migrate_player.
+async fn migrate_player(owner: &Keypair, data: Vec<u8>, total_players: u64) -> PlayerState {
+    let (player, _) =
+        Pubkey::find_program_address(&[PLAYER_SEED, owner.pubkey().as_ref()], &state_module::ID);
+    let (registry, registry_bump) =
+        Pubkey::find_program_address(&[REGISTRY_SEED], &state_module::ID);
+    let mut registry_data = Vec::new();
+    Registry { total_players, bump: registry_bump }
+        .try_serialize(&mut registry_data)
+        .unwrap();
+
+    let mut test = common::program_test();
+    test.add_account(player, program_account(data));
+    test.add_account(registry, program_account(registry_data));
+    test.add_account(owner.pubkey(), Account::new(10 * SOL, 0, &system_program::ID));
+    let (mut banks, payer, blockhash) = test.start().await;
+
+    let migrate = Instruction {
+        program_id: state_module::ID,
+        accounts: state_module::accounts::MigratePlayer {
+            player,
+            registry,
+            owner: owner.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: state_module::instruction::MigratePlayer {}.data(),
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[migrate],
+        Some(&payer.pubkey()),
+        &[&payer, owner],
+        blockhash,
+    );
+    banks.process_transaction(tx).await.unwrap();
+
+    let account = banks.get_account(player).await.unwrap().unwrap();
+    let migrated = PlayerState::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(account.data.len(), PlayerState::space(&migrated.name, &migrated.metadata_uri));
+    assert!(account.lamports >= Rent::default().minimum_balance(account.data.len()));
+    assert_eq!(migrated.version, PLAYER_VERSION);
+    migrated
+}
+
+#[tokio::test]
+async fn migrate_player_upgrades_a_version_1_account() {
+    let owner = Keypair::new();
+    let (_, bump) =
+        Pubkey::find_program_address(&[PLAYER_SEED, owner.pubkey().as_ref()], &state_module::ID);
+    let name = "founder";
+
+    // Version 1 ended at unspent_points, and its space reserved 33 bytes for the guild.
+    let mut legacy = PlayerState::DISCRIMINATOR.to_vec();
+    legacy.push(1); // version
+    legacy.extend_from_slice(owner.pubkey().as_ref());
+    legacy.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    legacy.extend_from_slice(name.as_bytes());
+    legacy.push(3); // level
+    legacy.push(bump);
+    legacy.extend_from_slice(&300u64.to_le_bytes()); // experience
+    legacy.extend_from_slice(&[90, 100, 10, 5, 50]); // hp, max_hp, attack_power, defense, mana
+    legacy.push(0); // locked
+    legacy.extend_from_slice(owner.pubkey().as_ref()); // registered_by
+    legacy.push(0); // guild
+    legacy.extend_from_slice(&[6, 7, 8, 1]); // strength, agility, vitality, unspent_points
+    legacy.resize(legacy.len() + 32, 0);
+
+    let migrated = migrate_player(&owner, legacy, 9).await;
+    assert_eq!(migrated.owner, owner.pubkey());
+    assert_eq!(migrated.name, name);
+    assert_eq!(migrated.level, 3);
+    assert_eq!(migrated.experience, 300);
+    assert_eq!((migrated.attack_power, migrated.defense, migrated.mana), (10, 5, 50));
+    assert_eq!(migrated.registered_by, owner.pubkey());
+    assert_eq!(migrated.guild, None);
+    assert_eq!(migrated.agility, 7);
+    assert_eq!(migrated.unspent_points, 1);
+    // Added after version 1: the index comes from the registry, the rest start empty.
+    assert_eq!(migrated.player_index, 9);
+    assert_eq!(migrated.achievements, 0);
+    assert_eq!(migrated.metadata_uri, "");
+    assert!(!migrated.frozen);
+    assert_eq!(migrated.gold, 0);
+    assert_eq!(migrated.controller, None);
+}
+
+#[tokio::test]
+async fn migrate_player_drops_hp() {
+    let owner = Keypair::new();
+    let (_, bump) =
+        Pubkey::find_program_address(&[PLAYER_SEED, owner.pubkey().as_ref()], &state_module::ID);
+    let name = "veteran".to_string();
+    let current = PlayerState {
+        version: PLAYER_VERSION,
+        owner: owner.pubkey(),
+        name: name.clone(),
+        level: 7,
+        bump,
+        experience: 4_900,
+        attack_power: 12,
+        defense: 6,
+        mana: 50,
+        locked: false,
+        registered_by: owner.pubkey(),
+        guild: None,
+        strength: 5,
+        agility: 5,
+        vitality: 5,
+        unspent_points: 2,
+        pending_owner: None,
+        player_index: 3,
+        created_at: 1,
+        last_active: 1,
+        achievements: 0b101,
+        metadata_uri: String::new(),
+        frozen: false,
+        equipment: EquipmentSlots::default(),
+        last_rename_ts: 0,
+        gold: 40,
+        controller: None,
+    };
+    // Version 11 stored hp and max_hp as a byte each, right after experience.
+    let mut legacy = Vec::new();
+    current.try_serialize(&mut legacy).unwrap();
+    let hp_offset = 8 + 1 + 32 + 4 + name.len() + 1 + 1 + 8;
+    legacy.splice(hp_offset..hp_offset, [180, 250]);
+    legacy[8] = PLAYER_VERSION - 1;
+
+    let migrated = migrate_player(&owner, legacy, 4).await;
+    assert_eq!(migrated.experience, 4_900);
+    assert_eq!(migrated.attack_power, 12);
+    assert_eq!(migrated.defense, 6);
+    assert_eq!(migrated.name, name);
+    assert_eq!(migrated.player_index, 3);
+    assert_eq!(migrated.achievements, 0b101);
+    assert_eq!(migrated.gold, 40);
+}
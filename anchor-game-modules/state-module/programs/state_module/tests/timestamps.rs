@@ -0,0 +1,106 @@
+mod common;
+
+use anchor_lang::{AccountDeserialize, AccountSerialize, InstructionData, ToAccountMetas};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+use state_module::{GameConfig, PlayerState, Registry, CONFIG_SEED, PLAYER_SEED, REGISTRY_SEED};
+
+const SOL: u64 = 1_000_000_000;
+
+fn program_account<T: AccountSerialize>(state: &T) -> Account {
+    let mut data = Vec::new();
+    state.try_serialize(&mut data).unwrap();
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: state_module::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+async fn send(context: &mut ProgramTestContext, instruction: Instruction, signer: &Keypair) {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, signer],
+        blockhash,
+    );
+    context.banks_client.process_transaction(tx).await.unwrap();
+}
+
+async fn fetch_player(context: &mut ProgramTestContext, player: Pubkey) -> PlayerState {
+    let account = context.banks_client.get_account(player).await.unwrap().unwrap();
+    PlayerState::try_deserialize(&mut &account.data[..]).unwrap()
+}
+
+#[tokio::test]
+async fn last_active_follows_the_clock() {
+    let owner = Keypair::new();
+    let (config, config_bump) = Pubkey::find_program_address(&[CONFIG_SEED], &state_module::ID);
+    let (registry, registry_bump) =
+        Pubkey::find_program_address(&[REGISTRY_SEED], &state_module::ID);
+    let (player, _) =
+        Pubkey::find_program_address(&[PLAYER_SEED, owner.pubkey().as_ref()], &state_module::ID);
+
+    let mut test = common::program_test();
+    test.add_account(
+        config,
+        program_account(&GameConfig {
+            admin: Pubkey::new_unique(),
+            max_level: 20,
+            base_hp: 100,
+            xp_multiplier: 100,
+            bump: config_bump,
+            attack_cooldown_secs: 0,
+        }),
+    );
+    test.add_account(
+        registry,
+        program_account(&Registry { total_players: 0, bump: registry_bump }),
+    );
+    test.add_account(owner.pubkey(), Account::new(10 * SOL, 0, &system_program::ID));
+    let mut context = test.start_with_context().await;
+
+    let register = Instruction {
+        program_id: state_module::ID,
+        accounts: state_module::accounts::RegisterPlayer {
+            player,
+            config,
+            registry,
+            authority: owner.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: state_module::instruction::RegisterPlayer { name: "clockwatcher".into() }.data(),
+    };
+    send(&mut context, register, &owner).await;
+    let registered = fetch_player(&mut context, player).await;
+    assert_eq!(registered.last_active, registered.created_at);
+
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += 3_600;
+    context.set_sysvar(&clock);
+
+    let initiate = Instruction {
+        program_id: state_module::ID,
+        accounts: state_module::accounts::TransferOwnership { player, owner: owner.pubkey() }
+            .to_account_metas(None),
+        data: state_module::instruction::InitiateOwnershipTransfer {
+            new_owner: Pubkey::new_unique(),
+        }
+        .data(),
+    };
+    send(&mut context, initiate, &owner).await;
+    let touched = fetch_player(&mut context, player).await;
+    assert_eq!(touched.created_at, registered.created_at);
+    assert_eq!(touched.last_active, registered.last_active + 3_600);
+}
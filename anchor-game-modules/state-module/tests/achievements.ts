@@ -0,0 +1,21 @@
+import { expect } from "chai";
+import { admin, configPda, expectError, newPlayer, program } from "./helpers";
+
+describe("achievements", () => {
+  it("sets the bit once and only fails on a repeat when strict", async () => {
+    const { player } = await newPlayer("achiever");
+    const grant = (id: number, strict: boolean) =>
+      program.methods
+        .grantAchievement(id, strict)
+        .accounts({ player, config: configPda, admin })
+        .rpc();
+
+    await grant(3, true);
+    expect((await program.account.playerState.fetch(player)).achievements.toNumber()).to.equal(8);
+
+    await grant(3, false);
+    await expectError(grant(3, true), "AlreadyUnlocked");
+    await expectError(grant(64, false), "AchievementOutOfRange");
+    expect((await program.account.playerState.fetch(player)).achievements.toNumber()).to.equal(8);
+  });
+});
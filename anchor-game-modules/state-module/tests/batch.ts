@@ -0,0 +1,134 @@
+import { expect } from "chai";
+import { Keypair, SystemProgram, Transaction } from "@solana/web3.js";
+import {
+  configPda,
+  ensureGame,
+  expectError,
+  fundedKeypair,
+  playerPda,
+  program,
+  provider,
+  registryPda,
+} from "./helpers";
+
+const batchAccounts = (owners: Keypair[], signers = owners) =>
+  owners.flatMap((owner) => [
+    {
+      pubkey: owner.publicKey,
+      isSigner: signers.includes(owner),
+      isWritable: false,
+    },
+    { pubkey: playerPda(owner.publicKey), isSigner: false, isWritable: true },
+  ]);
+
+describe("batch registration", () => {
+  before(ensureGame);
+
+  it("registers every signing owner in one transaction", async () => {
+    const owners = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    // Anyone can send lamports to a PDA ahead of time; that must not block registration.
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: provider.wallet.publicKey,
+          toPubkey: playerPda(owners[2].publicKey),
+          lamports: 1_000,
+        })
+      )
+    );
+
+    await program.methods
+      .registerPlayersBatch(["alpha", "bravo", "charlie"])
+      .accounts({ config: configPda, registry: registryPda, authority: provider.wallet.publicKey })
+      .remainingAccounts(batchAccounts(owners))
+      .signers(owners)
+      .rpc();
+
+    const names = ["alpha", "bravo", "charlie"];
+    for (const [i, owner] of owners.entries()) {
+      const player = await program.account.playerState.fetch(playerPda(owner.publicKey));
+      expect(player.owner).to.deep.equal(owner.publicKey);
+      expect(player.name).to.equal(names[i]);
+    }
+  });
+
+  it("rejects a batch where an owner hasn't signed", async () => {
+    const owners = [Keypair.generate(), Keypair.generate()];
+    await expectError(
+      program.methods
+        .registerPlayersBatch(["delta", "echo"])
+        .accounts({
+          config: configPda,
+          registry: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .remainingAccounts(batchAccounts(owners, [owners[0]]))
+        .signers([owners[0]])
+        .rpc(),
+      "OwnerMustSign"
+    );
+    expect(await provider.connection.getAccountInfo(playerPda(owners[0].publicKey))).to.equal(
+      null
+    );
+  });
+
+  it("rejects an owner who is already registered", async () => {
+    const owner = await fundedKeypair();
+    await program.methods
+      .registerPlayersBatch(["foxtrot"])
+      .accounts({ config: configPda, registry: registryPda, authority: provider.wallet.publicKey })
+      .remainingAccounts(batchAccounts([owner]))
+      .signers([owner])
+      .rpc();
+    await expectError(
+      program.methods
+        .registerPlayersBatch(["golf"])
+        .accounts({
+          config: configPda,
+          registry: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .remainingAccounts(batchAccounts([owner]))
+        .signers([owner])
+        .rpc(),
+      "PlayerAlreadyExists"
+    );
+  });
+
+  it("fails as a whole when any name is invalid", async () => {
+    const owners = [Keypair.generate(), Keypair.generate()];
+    await expectError(
+      program.methods
+        .registerPlayersBatch(["hotel", "   "])
+        .accounts({
+          config: configPda,
+          registry: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .remainingAccounts(batchAccounts(owners))
+        .signers(owners)
+        .rpc(),
+      "NameEmpty"
+    );
+    expect(await provider.connection.getAccountInfo(playerPda(owners[0].publicKey))).to.equal(
+      null
+    );
+  });
+
+  it("caps a batch at MAX_BATCH_REGISTRATIONS players", async () => {
+    const owners = Array.from({ length: 6 }, () => Keypair.generate());
+    await expectError(
+      program.methods
+        .registerPlayersBatch(owners.map((_, i) => `cohort-${i}`))
+        .accounts({
+          config: configPda,
+          registry: registryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .remainingAccounts(batchAccounts(owners))
+        .signers(owners)
+        .rpc(),
+      "BatchTooLarge"
+    );
+  });
+});
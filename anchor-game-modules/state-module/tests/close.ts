@@ -0,0 +1,67 @@
+import { expect } from "chai";
+import {
+  addInventoryItem,
+  expectError,
+  fundedKeypair,
+  inventoryPda,
+  newPlayer,
+  program,
+  provider,
+  TestPlayer,
+} from "./helpers";
+
+const close = ({ owner, player }: TestPlayer) =>
+  program.methods
+    .closePlayer()
+    .accounts({ player, inventory: inventoryPda(player), owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+describe("close_player", () => {
+  it("only lets the owner close the player", async () => {
+    const { player } = await newPlayer("squatter");
+    const stranger = await fundedKeypair();
+    await expectError(close({ owner: stranger, player }), "ConstraintHasOne");
+    expect(await provider.connection.getAccountInfo(player)).to.not.equal(null);
+  });
+
+  it("refunds the rent to the owner and removes the account", async () => {
+    const tourist = await newPlayer("tourist");
+    const { owner, player } = tourist;
+    const rent = (await provider.connection.getAccountInfo(player))!.lamports;
+    const before = await provider.connection.getBalance(owner.publicKey);
+
+    await close(tourist);
+
+    // The provider wallet pays the fee, so the owner's balance grows by exactly the rent.
+    expect(await provider.connection.getBalance(owner.publicKey)).to.equal(before + rent);
+    expect(await provider.connection.getAccountInfo(player)).to.equal(null);
+    await expectError(program.account.playerState.fetch(player), "Account does not exist");
+  });
+
+  it("refuses while the inventory holds items and closes it once emptied", async () => {
+    const hoarder = await newPlayer("hoarder");
+    const { owner, player } = hoarder;
+    const inventory = inventoryPda(player);
+    const mint = await addInventoryItem(hoarder);
+
+    await expectError(close(hoarder), "InventoryNotEmpty");
+    expect(await provider.connection.getAccountInfo(player)).to.not.equal(null);
+
+    await program.methods
+      .removeItemFromInventory(mint)
+      .accounts({ player, inventory, owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    const rent =
+      (await provider.connection.getAccountInfo(player))!.lamports +
+      (await provider.connection.getAccountInfo(inventory))!.lamports;
+    const before = await provider.connection.getBalance(owner.publicKey);
+
+    await close(hoarder);
+
+    expect(await provider.connection.getBalance(owner.publicKey)).to.equal(before + rent);
+    expect(await provider.connection.getAccountInfo(player)).to.equal(null);
+    expect(await provider.connection.getAccountInfo(inventory)).to.equal(null);
+  });
+});
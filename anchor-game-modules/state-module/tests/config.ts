@@ -0,0 +1,47 @@
+import { expect } from "chai";
+import {
+  admin,
+  configParams,
+  configPda,
+  ensureGame,
+  expectError,
+  fundedKeypair,
+  program,
+  programDataPda,
+  provider,
+} from "./helpers";
+
+describe("config", () => {
+  it("only lets the upgrade authority create the config", async function () {
+    // Skipped if another test file already created the config.
+    if (await provider.connection.getAccountInfo(configPda)) {
+      this.skip();
+    }
+    const stranger = await fundedKeypair();
+    await expectError(
+      program.methods
+        .initializeConfig(configParams(stranger.publicKey))
+        .accounts({
+          config: configPda,
+          authority: stranger.publicKey,
+          program: program.programId,
+          programData: programDataPda,
+        })
+        .signers([stranger])
+        .rpc(),
+      "NotUpgradeAuthority"
+    );
+
+    await ensureGame();
+    const config = await program.account.gameConfig.fetch(configPda);
+    expect(config.admin.toBase58()).to.equal(admin.toBase58());
+  });
+
+  it("leaves a current config alone on migrate_config", async () => {
+    await ensureGame();
+    const before = await provider.connection.getAccountInfo(configPda);
+    await program.methods.migrateConfig().accounts({ config: configPda, admin }).rpc();
+    const after = await provider.connection.getAccountInfo(configPda);
+    expect(after.data.equals(before.data)).to.equal(true);
+  });
+});
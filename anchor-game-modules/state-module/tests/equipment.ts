@@ -0,0 +1,72 @@
+import { expect } from "chai";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  addInventoryItem,
+  expectError,
+  inventoryPda,
+  newPlayer,
+  program,
+  TestPlayer,
+} from "./helpers";
+
+type Slot = Parameters<typeof program.methods.unequipItem>[0];
+const WEAPON: Slot = { weapon: {} };
+const ARMOR: Slot = { armor: {} };
+
+const equip = ({ owner, player }: TestPlayer, slot: Slot, mint: PublicKey) =>
+  program.methods
+    .equipItem(slot, mint)
+    .accounts({ player, inventory: inventoryPda(player), owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+const unequip = ({ owner, player }: TestPlayer, slot: Slot) =>
+  program.methods
+    .unequipItem(slot)
+    .accounts({ player, owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+const equipmentOf = async ({ player }: TestPlayer) =>
+  (await program.account.playerState.fetch(player)).equipment;
+
+describe("equipment", () => {
+  it("equips and unequips an item from the inventory", async () => {
+    const player = await newPlayer("squire");
+    const sword = await addInventoryItem(player);
+
+    await equip(player, WEAPON, sword);
+    expect((await equipmentOf(player)).weapon?.toBase58()).to.equal(sword.toBase58());
+
+    await unequip(player, WEAPON);
+    expect((await equipmentOf(player)).weapon).to.be.null;
+  });
+
+  it("rejects equipping a mint that isn't in the inventory", async () => {
+    const player = await newPlayer("pretender");
+    await addInventoryItem(player);
+
+    await expectError(equip(player, WEAPON, Keypair.generate().publicKey), "ItemNotFound");
+    expect((await equipmentOf(player)).weapon).to.be.null;
+  });
+
+  it("rejects equipping into a slot that already holds an item", async () => {
+    const player = await newPlayer("hoarder");
+    const sword = await addInventoryItem(player);
+    const axe = await addInventoryItem(player);
+    await equip(player, WEAPON, sword);
+
+    await expectError(equip(player, WEAPON, axe), "SlotOccupied");
+    const equipment = await equipmentOf(player);
+    expect(equipment.weapon?.toBase58()).to.equal(sword.toBase58());
+
+    // Other slots are unaffected.
+    await equip(player, ARMOR, axe);
+    expect((await equipmentOf(player)).armor?.toBase58()).to.equal(axe.toBase58());
+  });
+
+  it("rejects unequipping an empty slot", async () => {
+    const player = await newPlayer("naked");
+    await expectError(unequip(player, ARMOR), "SlotEmpty");
+  });
+});
@@ -0,0 +1,46 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import {
+  admin,
+  configPda,
+  expectError,
+  leaderboardPda,
+  MAX_LEVEL,
+  newPlayer,
+  program,
+} from "./helpers";
+
+describe("experience", () => {
+  it("only lets the admin grant experience", async () => {
+    const { owner, player } = await newPlayer("grinder");
+    await expectError(
+      program.methods
+        .addExperience(new BN(1_000_000))
+        .accounts({
+          player,
+          config: configPda,
+          leaderboard: leaderboardPda,
+          admin: owner.publicKey,
+        })
+        .signers([owner])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+    expect((await program.account.playerState.fetch(player)).level).to.equal(1);
+  });
+
+  it("levels up from admin grants and stops at max_level", async () => {
+    const { player } = await newPlayer("veteran");
+    await program.methods
+      .addExperience(new BN(100))
+      .accounts({ player, config: configPda, leaderboard: leaderboardPda, admin })
+      .rpc();
+    expect((await program.account.playerState.fetch(player)).level).to.equal(2);
+
+    await program.methods
+      .addExperience(new BN("1000000000000"))
+      .accounts({ player, config: configPda, leaderboard: leaderboardPda, admin })
+      .rpc();
+    expect((await program.account.playerState.fetch(player)).level).to.equal(MAX_LEVEL);
+  });
+});
@@ -0,0 +1,37 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { admin, configPda, expectError, leaderboardPda, newPlayer, program } from "./helpers";
+
+describe("freeze_player", () => {
+  it("blocks a frozen player's mutations until the admin unfreezes it", async () => {
+    const { owner, player } = await newPlayer("suspect");
+    const freeze = () =>
+      program.methods.freezePlayer().accounts({ player, config: configPda, admin }).rpc();
+    const unfreeze = () =>
+      program.methods.unfreezePlayer().accounts({ player, config: configPda, admin }).rpc();
+    const grantXp = () =>
+      program.methods
+        .addExperience(new BN(100))
+        .accounts({ player, config: configPda, leaderboard: leaderboardPda, admin })
+        .rpc();
+
+    await expectError(
+      program.methods
+        .freezePlayer()
+        .accounts({ player, config: configPda, admin: owner.publicKey })
+        .signers([owner])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+
+    await freeze();
+    // Freezing twice is a no-op rather than an error.
+    await freeze();
+    await expectError(grantXp(), "PlayerFrozen");
+    expect((await program.account.playerState.fetch(player)).level).to.equal(1);
+
+    await unfreeze();
+    await grantXp();
+    expect((await program.account.playerState.fetch(player)).level).to.equal(2);
+  });
+});
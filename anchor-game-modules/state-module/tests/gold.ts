@@ -0,0 +1,68 @@
+import { expect } from "chai";
+import { BN } from "@coral-xyz/anchor";
+import { admin, configPda, expectError, newPlayer, program, TestPlayer } from "./helpers";
+
+const grantGold = (player: TestPlayer, amount: number) =>
+  program.methods
+    .grantGold(new BN(amount))
+    .accounts({ player: player.player, config: configPda, admin })
+    .rpc();
+
+const transferGold = (sender: TestPlayer, recipient: TestPlayer, amount: number) =>
+  program.methods
+    .transferGold(new BN(amount))
+    .accounts({
+      sender: sender.player,
+      recipient: recipient.player,
+      owner: sender.owner.publicKey,
+    })
+    .signers([sender.owner])
+    .rpc();
+
+const goldOf = async (player: TestPlayer) =>
+  (await program.account.playerState.fetch(player.player)).gold.toNumber();
+
+describe("gold", () => {
+  it("only lets the admin grant gold", async () => {
+    const player = await newPlayer("prospector");
+    await expectError(
+      program.methods
+        .grantGold(new BN(100))
+        .accounts({ player: player.player, config: configPda, admin: player.owner.publicKey })
+        .signers([player.owner])
+        .rpc(),
+      "ConstraintHasOne"
+    );
+
+    await grantGold(player, 100);
+    expect(await goldOf(player)).to.equal(100);
+  });
+
+  it("moves gold from the sender to the recipient", async () => {
+    const sender = await newPlayer("merchant");
+    const recipient = await newPlayer("customer");
+    await grantGold(sender, 100);
+
+    await transferGold(sender, recipient, 40);
+    expect(await goldOf(sender)).to.equal(60);
+    expect(await goldOf(recipient)).to.equal(40);
+  });
+
+  it("rejects spending more than the sender holds", async () => {
+    const sender = await newPlayer("spendthrift");
+    const recipient = await newPlayer("creditor");
+    await grantGold(sender, 10);
+
+    await expectError(transferGold(sender, recipient, 11), "InsufficientGold");
+    expect(await goldOf(sender)).to.equal(10);
+    expect(await goldOf(recipient)).to.equal(0);
+  });
+
+  it("rejects sending gold to yourself", async () => {
+    const player = await newPlayer("narcissus");
+    await grantGold(player, 10);
+
+    await expectError(transferGold(player, player, 5), "SelfTransfer");
+    expect(await goldOf(player)).to.equal(10);
+  });
+});
@@ -0,0 +1,82 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import { expectError, newPlayer, pda, program, provider, TestPlayer } from "./helpers";
+
+const guildPda = (name: string) => pda([Buffer.from("guild"), Buffer.from(name)]);
+const memberPda = (guild: PublicKey, player: PublicKey) =>
+  pda([Buffer.from("member"), guild.toBuffer(), player.toBuffer()]);
+
+const memberAccounts = (guild: PublicKey, member: TestPlayer) => ({
+  player: member.player,
+  guild,
+  membership: memberPda(guild, member.player),
+  owner: member.owner.publicKey,
+});
+
+async function createGuild(name: string, capacity: number, leader: TestPlayer) {
+  const guild = guildPda(name);
+  await program.methods
+    .createGuild(name, capacity)
+    .accounts(memberAccounts(guild, leader))
+    .signers([leader.owner])
+    .rpc();
+  return guild;
+}
+
+const join = (guild: PublicKey, member: TestPlayer) =>
+  program.methods
+    .joinGuild()
+    .accounts(memberAccounts(guild, member))
+    .signers([member.owner])
+    .rpc();
+
+const leave = (guild: PublicKey, member: TestPlayer) =>
+  program.methods
+    .leaveGuild()
+    .accounts(memberAccounts(guild, member))
+    .signers([member.owner])
+    .rpc();
+
+const disband = (guild: PublicKey, leader: TestPlayer) =>
+  program.methods
+    .disbandGuild()
+    .accounts(memberAccounts(guild, leader))
+    .signers([leader.owner])
+    .rpc();
+
+describe("guilds", () => {
+  it("fills up to capacity and no further", async () => {
+    const leader = await newPlayer("guild-lead");
+    const guild = await createGuild("full house", 2, leader);
+    await join(guild, await newPlayer("guild-second"));
+    expect((await program.account.guild.fetch(guild)).memberCount).to.equal(2);
+    await expectError(join(guild, await newPlayer("guild-third")), "GuildFull");
+  });
+
+  it("makes the leader hand over or disband before leaving", async () => {
+    const leader = await newPlayer("founder");
+    const member = await newPlayer("heir");
+    const guild = await createGuild("dynasty", 5, leader);
+    await join(guild, member);
+
+    await expectError(leave(guild, leader), "LeaderCannotLeave");
+    await expectError(disband(guild, leader), "GuildNotEmpty");
+
+    await program.methods
+      .transferGuildLeadership()
+      .accounts({
+        player: leader.player,
+        guild,
+        newLeaderMembership: memberPda(guild, member.player),
+        owner: leader.owner.publicKey,
+      })
+      .signers([leader.owner])
+      .rpc();
+    await leave(guild, leader);
+    expect((await program.account.playerState.fetch(leader.player)).guild).to.equal(null);
+
+    await disband(guild, member);
+    expect(await provider.connection.getAccountInfo(guild)).to.equal(null);
+    expect((await program.account.playerState.fetch(member.player)).guild).to.equal(null);
+  });
+});
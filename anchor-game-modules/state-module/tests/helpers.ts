@@ -0,0 +1,139 @@
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import { createMint, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import { StateModule } from "../target/types/state_module";
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+
+export const program = anchor.workspace.StateModule as Program<StateModule>;
+export const admin = provider.wallet.publicKey;
+
+export const pda = (seeds: (Buffer | Uint8Array)[]) =>
+  PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+export const configPda = pda([Buffer.from("config")]);
+export const registryPda = pda([Buffer.from("registry")]);
+export const leaderboardPda = pda([Buffer.from("leaderboard")]);
+export const playerPda = (owner: PublicKey) => pda([Buffer.from("player"), owner.toBuffer()]);
+export const inventoryPda = (player: PublicKey) =>
+  pda([Buffer.from("inventory"), player.toBuffer()]);
+export const programDataPda = PublicKey.findProgramAddressSync(
+  [program.programId.toBuffer()],
+  new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+)[0];
+
+export const MAX_LEVEL = 20;
+
+export const configParams = (configAdmin: PublicKey) => ({
+  admin: configAdmin,
+  maxLevel: MAX_LEVEL,
+  baseHp: 100,
+  xpMultiplier: 100,
+  attackCooldownSecs: new BN(0),
+});
+
+export type TestPlayer = {
+  owner: Keypair;
+  player: PublicKey;
+};
+
+export async function fundedKeypair(sol = 10): Promise<Keypair> {
+  const keypair = Keypair.generate();
+  const signature = await provider.connection.requestAirdrop(
+    keypair.publicKey,
+    sol * LAMPORTS_PER_SOL
+  );
+  await provider.connection.confirmTransaction(signature);
+  return keypair;
+}
+
+// Creates the config, registry and leaderboard the first time any test file needs them. The
+// provider wallet is the upgrade authority (see Anchor.toml) and the game admin.
+export async function ensureGame() {
+  if (await provider.connection.getAccountInfo(configPda)) {
+    return;
+  }
+  await program.methods
+    .initializeConfig(configParams(admin))
+    .accounts({
+      config: configPda,
+      authority: admin,
+      program: program.programId,
+      programData: programDataPda,
+    })
+    .rpc();
+  await program.methods
+    .initializeRegistry()
+    .accounts({ registry: registryPda, payer: admin })
+    .rpc();
+  await program.methods
+    .initializeLeaderboard()
+    .accounts({ leaderboard: leaderboardPda, payer: admin })
+    .rpc();
+}
+
+export async function newPlayer(name: string): Promise<TestPlayer> {
+  await ensureGame();
+  const owner = await fundedKeypair();
+  const player = playerPda(owner.publicKey);
+  await program.methods
+    .registerPlayer(name)
+    .accounts({
+      player,
+      config: configPda,
+      registry: registryPda,
+      authority: owner.publicKey,
+    })
+    .signers([owner])
+    .rpc();
+  return { owner, player };
+}
+
+// Mints one token of a fresh mint to the player's owner and adds it to their inventory,
+// creating the inventory first if needed. Returns the mint.
+export async function addInventoryItem({ owner, player }: TestPlayer): Promise<PublicKey> {
+  const inventory = inventoryPda(player);
+  if (!(await provider.connection.getAccountInfo(inventory))) {
+    await program.methods
+      .initInventory()
+      .accounts({ player, inventory, owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+  }
+  const mint = await createMint(provider.connection, owner, owner.publicKey, null, 0);
+  const tokenAccount = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    owner,
+    mint,
+    owner.publicKey
+  );
+  await mintTo(provider.connection, owner, mint, tokenAccount.address, owner, 1);
+  await program.methods
+    .addItemToInventory()
+    .accounts({
+      player,
+      inventory,
+      mint,
+      tokenAccount: tokenAccount.address,
+      owner: owner.publicKey,
+    })
+    .signers([owner])
+    .rpc();
+  return mint;
+}
+
+// Resolves once `promise` fails with the named program error.
+export async function expectError(promise: Promise<unknown>, code: string) {
+  try {
+    await promise;
+  } catch (err) {
+    const actual = err instanceof anchor.AnchorError ? err.error.errorCode.code : String(err);
+    if (!actual.includes(code)) {
+      throw new Error(`expected ${code}, got ${actual}`);
+    }
+    return;
+  }
+  throw new Error(`expected ${code}, but the transaction succeeded`);
+}
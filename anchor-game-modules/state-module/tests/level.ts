@@ -0,0 +1,84 @@
+import { expect } from "chai";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  admin,
+  configPda,
+  expectError,
+  fundedKeypair,
+  leaderboardPda,
+  MAX_LEVEL,
+  newPlayer,
+  program,
+  TestPlayer,
+} from "./helpers";
+
+describe("update_level", () => {
+  const setLevel = (player: PublicKey, level: number) =>
+    program.methods
+      .updateLevel(level)
+      .accounts({ player, config: configPda, leaderboard: leaderboardPda, authority: admin })
+      .rpc();
+
+  const setLevelAs = (authority: Keypair, player: PublicKey, level: number) =>
+    program.methods
+      .updateLevel(level)
+      .accounts({
+        player,
+        config: configPda,
+        leaderboard: leaderboardPda,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+
+  const setController = ({ owner, player }: TestPlayer, controller: PublicKey | null) =>
+    program.methods
+      .setController(controller)
+      .accounts({ player, owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+  const levelOf = async (player: PublicKey) =>
+    (await program.account.playerState.fetch(player)).level;
+
+  it("accepts exactly max_level and rejects one above it", async () => {
+    const { player } = await newPlayer("climber");
+    await expectError(setLevel(player, MAX_LEVEL + 1), "LevelTooHigh");
+    await setLevel(player, MAX_LEVEL);
+    expect((await program.account.playerState.fetch(player)).level).to.equal(MAX_LEVEL);
+  });
+
+  it("rejects setting the current level again", async () => {
+    const { player } = await newPlayer("idler");
+    await setLevel(player, 5);
+    await expectError(setLevel(player, 5), "LevelUnchanged");
+  });
+
+  it("rejects level 0, since registration starts at 1", async () => {
+    const { player } = await newPlayer("zeroed");
+    await expectError(setLevel(player, 0), "InvalidLevel");
+    expect((await program.account.playerState.fetch(player)).level).to.equal(1);
+  });
+
+  it("lets the player's controller set the level until it is revoked", async () => {
+    const player = await newPlayer("puppet");
+    const controller = await fundedKeypair();
+    await setController(player, controller.publicKey);
+
+    await setLevelAs(controller, player.player, 4);
+    expect(await levelOf(player.player)).to.equal(4);
+
+    await setController(player, null);
+    await expectError(setLevelAs(controller, player.player, 6), "NotLevelAuthority");
+    expect(await levelOf(player.player)).to.equal(4);
+  });
+
+  it("rejects anyone but the admin or the controller", async () => {
+    const player = await newPlayer("guarded");
+    await setController(player, (await fundedKeypair()).publicKey);
+
+    await expectError(setLevelAs(player.owner, player.player, 3), "NotLevelAuthority");
+    await expectError(setLevelAs(await fundedKeypair(), player.player, 3), "NotLevelAuthority");
+    expect(await levelOf(player.player)).to.equal(1);
+  });
+});
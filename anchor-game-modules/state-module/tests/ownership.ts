@@ -0,0 +1,63 @@
+import { expect } from "chai";
+import { PublicKey } from "@solana/web3.js";
+import { expectError, fundedKeypair, newPlayer, program } from "./helpers";
+
+describe("ownership transfer", () => {
+  it("moves the player once the pending owner accepts", async () => {
+    const { owner, player } = await newPlayer("seller");
+    const buyer = await fundedKeypair();
+    await program.methods
+      .initiateOwnershipTransfer(buyer.publicKey)
+      .accounts({ player, owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    expect((await program.account.playerState.fetch(player)).pendingOwner).to.deep.equal(
+      buyer.publicKey
+    );
+
+    await program.methods
+      .acceptOwnershipTransfer()
+      .accounts({ player, newOwner: buyer.publicKey })
+      .signers([buyer])
+      .rpc();
+    const state = await program.account.playerState.fetch(player);
+    expect(state.owner).to.deep.equal(buyer.publicKey);
+    expect(state.pendingOwner).to.equal(null);
+  });
+
+  it("rejects an accept from anyone but the pending owner", async () => {
+    const { owner, player } = await newPlayer("cautious");
+    const buyer = await fundedKeypair();
+    const stranger = await fundedKeypair();
+    await program.methods
+      .initiateOwnershipTransfer(buyer.publicKey)
+      .accounts({ player, owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+    await expectError(
+      program.methods
+        .acceptOwnershipTransfer()
+        .accounts({ player, newOwner: stranger.publicKey })
+        .signers([stranger])
+        .rpc(),
+      "NotPendingOwner"
+    );
+    expect((await program.account.playerState.fetch(player)).owner).to.deep.equal(
+      owner.publicKey
+    );
+  });
+
+  it("rejects transfers to the zero key or the current owner", async () => {
+    const { owner, player } = await newPlayer("stubborn");
+    for (const target of [PublicKey.default, owner.publicKey]) {
+      await expectError(
+        program.methods
+          .initiateOwnershipTransfer(target)
+          .accounts({ player, owner: owner.publicKey })
+          .signers([owner])
+          .rpc(),
+        "InvalidNewOwner"
+      );
+    }
+  });
+});
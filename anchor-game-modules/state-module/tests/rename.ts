@@ -0,0 +1,54 @@
+import { expect } from "chai";
+import { expectError, newPlayer, program, provider, TestPlayer } from "./helpers";
+
+const rename = ({ owner, player }: TestPlayer, name: string) =>
+  program.methods
+    .updatePlayerName(name)
+    .accounts({ player, owner: owner.publicKey })
+    .signers([owner])
+    .rpc();
+
+// The provider wallet pays every fee, so the owner's balance only moves by the realloc's rent.
+const balances = async ({ owner, player }: TestPlayer) => {
+  const account = await provider.connection.getAccountInfo(player);
+  return {
+    size: account!.data.length,
+    rent: account!.lamports,
+    owner: await provider.connection.getBalance(owner.publicKey),
+  };
+};
+
+describe("update_player_name", () => {
+  it("grows the account for a longer name, paid by the owner", async () => {
+    const player = await newPlayer("ann");
+    const before = await balances(player);
+
+    await rename(player, "annabelle-the-bold");
+    const after = await balances(player);
+    expect(after.size - before.size).to.equal("annabelle-the-bold".length - "ann".length);
+    expect(after.rent).to.be.greaterThan(before.rent);
+    expect(before.owner - after.owner).to.equal(after.rent - before.rent);
+    expect((await program.account.playerState.fetch(player.player)).name).to.equal(
+      "annabelle-the-bold"
+    );
+  });
+
+  it("shrinks the account for a shorter name and refunds the rent to the owner", async () => {
+    const player = await newPlayer("bartholomew-the-tall");
+    const before = await balances(player);
+
+    await rename(player, "bart");
+    const after = await balances(player);
+    expect(before.size - after.size).to.equal("bartholomew-the-tall".length - "bart".length);
+    expect(after.rent).to.be.lessThan(before.rent);
+    expect(after.owner - before.owner).to.equal(before.rent - after.rent);
+  });
+
+  it("rejects a second rename within the cooldown", async () => {
+    const player = await newPlayer("fickle");
+    await rename(player, "capricious");
+
+    await expectError(rename(player, "steadfast"), "RenameOnCooldown");
+    expect((await program.account.playerState.fetch(player.player)).name).to.equal("capricious");
+  });
+});
@@ -0,0 +1,56 @@
+// Checks that every tool calling an Anchor program passes the accounts its instruction's IDL
+// expects. Run after `anchor build` in each module so the IDLs under target/idl are current.
+import fs from 'fs';
+import path from 'path';
+
+const toolsDir = 'src/tools';
+// Anchor's client fills these in when they are left out.
+const resolved = new Set(['systemProgram', 'tokenProgram', 'associatedTokenProgram', 'rent']);
+
+// Returns the text between the brace after `from` and the brace that closes it.
+function block(source, from) {
+  const start = source.indexOf('{', from);
+  let depth = 0;
+  for (let i = start; i < source.length; i++) {
+    if (source[i] === '{') depth++;
+    if (source[i] === '}' && --depth === 0) return source.slice(start + 1, i);
+  }
+  throw new Error('unbalanced braces');
+}
+
+let failures = 0;
+for (const tool of fs.readdirSync(toolsDir)) {
+  const file = path.join(toolsDir, tool, 'index.js');
+  const source = fs.readFileSync(file, 'utf8');
+  const idlImport = source.match(/import idl from '([^']+\.json)'/);
+  const call = /\.methods\s*\.(\w+)\((?:[^()]|\([^()]*\))*\)\s*\.accounts\(/.exec(source);
+  if (!idlImport || !call) continue;
+
+  const idl = JSON.parse(fs.readFileSync(path.resolve(path.dirname(file), idlImport[1]), 'utf8'));
+  const instruction = idl.instructions.find(ix => ix.name === call[1]);
+  if (!instruction) {
+    console.error(`✗ ${tool}: ${call[1]} is not in ${idl.name}'s IDL`);
+    failures++;
+    continue;
+  }
+  const passed = new Set(
+    block(source, call.index + call[0].length - 1)
+      .split('\n')
+      .map(line => line.match(/^\s*(\w+)\s*(?::|,|$)/))
+      .filter(Boolean)
+      .map(match => match[1])
+  );
+  const expected = instruction.accounts;
+  const missing = expected
+    .filter(account => !account.isOptional && !resolved.has(account.name))
+    .filter(account => !passed.has(account.name))
+    .map(account => account.name);
+  const unknown = [...passed].filter(name => !expected.some(account => account.name === name));
+  if (missing.length || unknown.length) {
+    console.error(`✗ ${tool}: ${call[1]} missing [${missing}] unknown [${unknown}]`);
+    failures++;
+  } else {
+    console.log(`✓ ${tool}: ${call[1]}`);
+  }
+}
+process.exit(failures ? 1 : 0);
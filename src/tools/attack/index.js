@@ -1,21 +1,23 @@
 import { z } from 'zod';
-import { Connection, Keypair, PublicKey } from '@solana/web3.js';
+import { Connection, Keypair, PublicKey, SYSVAR_SLOT_HASHES_PUBKEY } from '@solana/web3.js';
 import { AnchorProvider, Program } from '@coral-xyz/anchor';
 import idl from '../../../anchor-game-modules/behavior-module/target/idl/behavior_module.json' assert { type: 'json' };
+import stateIdl from '../../../anchor-game-modules/state-module/target/idl/state_module.json' assert { type: 'json' };
 
 const connection = new Connection("https://api.devnet.solana.com");
 const programId = new PublicKey(idl.metadata.address);
+const stateProgramId = new PublicKey(stateIdl.metadata.address);
 
 export const attack = {
   name: 'attack',
-  description: 'Simulate an attack: reduce player HP by a given amount',
+  description: 'Attack another player; damage is derived from attacker and defender stats',
   schema: z.object({
     secretKey: z.array(z.number()).min(64),
-    player: z.string(),
-    damage: z.number().min(1).max(255)
+    attacker: z.string(),
+    defender: z.string()
   }),
   permissions: ['public'],
-  run: async ({ secretKey, player, damage }) => {
+  run: async ({ secretKey, attacker, defender }) => {
     const payer = Keypair.fromSecretKey(Uint8Array.from(secretKey));
     const provider = new AnchorProvider(connection, {
       publicKey: payer.publicKey,
@@ -23,8 +25,29 @@ export const attack = {
     }, {});
     const program = new Program(idl, programId, provider);
 
-    const tx = await program.methods.attack(damage).accounts({
-      player: new PublicKey(player),
+    const attackerKey = new PublicKey(attacker);
+    const defenderKey = new PublicKey(defender);
+    const combatPDA = (player) => PublicKey.findProgramAddressSync(
+      [Buffer.from("combat"), player.toBuffer()],
+      program.programId
+    )[0];
+    const [configPDA] = PublicKey.findProgramAddressSync([Buffer.from("config")], stateProgramId);
+    const [leaderboardPDA] = PublicKey.findProgramAddressSync([Buffer.from("leaderboard")], stateProgramId);
+    const [combatAuthorityPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("combat_authority")],
+      program.programId
+    );
+
+    const tx = await program.methods.attack(false).accounts({
+      attacker: attackerKey,
+      attackerCombat: combatPDA(attackerKey),
+      defender: defenderKey,
+      defenderCombat: combatPDA(defenderKey),
+      config: configPDA,
+      leaderboard: leaderboardPDA,
+      combatAuthority: combatAuthorityPDA,
+      slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+      stateProgram: stateProgramId,
       owner: payer.publicKey
     }).signers([payer]).rpc();
 
@@ -1,6 +1,7 @@
 import { z } from 'zod';
 import { Connection, PublicKey, Keypair, Transaction } from '@solana/web3.js';
 import { Program, AnchorProvider, web3 } from '@coral-xyz/anchor';
+import { getAssociatedTokenAddressSync, ASSOCIATED_TOKEN_PROGRAM_ID } from '@solana/spl-token';
 import idl from '../../../anchor-game-modules/asset-module/target/idl/asset_module.json' assert { type: 'json' };
 import fs from 'fs';
 
@@ -13,21 +14,55 @@ export const gameMintItem = {
   schema: z.object({
     mint: z.string(),
     to: z.string(),
+    amount: z.number().int().min(1).default(1),
     secretKey: z.array(z.number()).min(64)
   }),
   permissions: ['public'],
-  run: async ({ mint, to, secretKey }) => {
+  run: async ({ mint, to, amount, secretKey }) => {
     const payer = Keypair.fromSecretKey(Uint8Array.from(secretKey));
     const provider = new AnchorProvider(connection, { publicKey: payer.publicKey, signTransaction: async tx => tx }, {});
     const program = new Program(idl, programID, provider);
 
+    const [mintConfig] = PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_config"), new PublicKey(mint).toBuffer()],
+      programID
+    );
+
+    const [mintAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_authority")],
+      programID
+    );
+
+    const [itemMetadata] = PublicKey.findProgramAddressSync(
+      [Buffer.from("item_metadata"), new PublicKey(mint).toBuffer()],
+      programID
+    );
+
+    const { itemType: itemTypeId } = await program.account.itemMetadata.fetch(itemMetadata);
+    const [itemType] = PublicKey.findProgramAddressSync(
+      [Buffer.from("item_type"), Buffer.from([itemTypeId])],
+      programID
+    );
+
+    const recipient = new PublicKey(to);
+    const ata = getAssociatedTokenAddressSync(new PublicKey(mint), recipient);
+
     const tx = await program.methods
-      .mintItem(new web3.BN(1))
+      .mintItem(new web3.BN(amount))
       .accounts({
         authority: payer.publicKey,
+        payer: payer.publicKey,
         mint: new PublicKey(mint),
-        to: new PublicKey(to),
-        tokenProgram: new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        mintConfig,
+        mintAuthority,
+        itemMetadata,
+        itemType,
+        recipient,
+        to: ata,
+        tokenProgram: new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+        rent: web3.SYSVAR_RENT_PUBKEY
       })
       .signers([payer])
       .rpc();
@@ -26,9 +26,19 @@ export const registerPlayer = {
       [Buffer.from("player"), payer.publicKey.toBuffer()],
       program.programId
     );
+    const [configPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const [registryPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("registry")],
+      program.programId
+    );
 
     const tx = await program.methods.registerPlayer(name).accounts({
       player: playerPDA,
+      config: configPDA,
+      registry: registryPDA,
       authority: payer.publicKey,
       systemProgram: web3.SystemProgram.programId
     }).signers([payer]).rpc();
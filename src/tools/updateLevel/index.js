@@ -8,7 +8,7 @@ const connection = new Connection('https://api.devnet.solana.com');
 
 export const updateLevel = {
   name: 'updateLevel',
-  description: 'Update player level',
+  description: 'Override a player level (game admin or the player\'s controller only)',
   schema: z.object({
     secretKey: z.array(z.number()).min(64),
     player: z.string(),
@@ -23,9 +23,21 @@ export const updateLevel = {
     }, {});
     const program = new Program(idl, programId, provider);
 
+    const [configPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+
+    const [leaderboardPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("leaderboard")],
+      program.programId
+    );
+
     const tx = await program.methods.updateLevel(level).accounts({
       player: new PublicKey(player),
-      owner: payer.publicKey
+      config: configPDA,
+      leaderboard: leaderboardPDA,
+      authority: payer.publicKey
     }).signers([payer]).rpc();
 
     return {